@@ -1,18 +1,24 @@
-use super::context::{from_hex_to_utf8_str, from_0x_hex};
-use serde::{de, Deserialize, Deserializer};
+use super::attrs::ActionAttrs;
+use super::context::{from_hex_to_utf8_str, from_0x_hex, to_hex_str, to_0x_hex};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::From;
+use std::fmt;
 
-#[derive(Clone)]
+/// Debot action type.
+///
+/// Unknown action types are kept in `Other` with their raw value,
+/// so action can be serialized back without loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AcType {
-    Empty = 0,
-    RunAction = 1,
-    RunMethod = 2,
-    SendMsg = 3,
-    Invoke = 4,
-    Print = 5,
-    Goto = 6,
-    CallEngine = 10,
-    Unknown = 255,
+    Empty,
+    RunAction,
+    RunMethod,
+    SendMsg,
+    Invoke,
+    Print,
+    Goto,
+    CallEngine,
+    Other(u8),
 }
 
 impl From<u8> for AcType {
@@ -26,25 +32,114 @@ impl From<u8> for AcType {
             5 => AcType::Print,
             6 => AcType::Goto,
             10 => AcType::CallEngine,
-            _ => AcType::Unknown,
+            _ => AcType::Other(ac_type),
         }
     }
 }
 
-#[derive(Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
+impl From<AcType> for u8 {
+    fn from(ac_type: AcType) -> Self {
+        match ac_type {
+            AcType::Empty => 0,
+            AcType::RunAction => 1,
+            AcType::RunMethod => 2,
+            AcType::SendMsg => 3,
+            AcType::Invoke => 4,
+            AcType::Print => 5,
+            AcType::Goto => 6,
+            AcType::CallEngine => 10,
+            AcType::Other(ac_type) => ac_type,
+        }
+    }
+}
+
+impl fmt::Display for AcType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AcType::Empty => write!(f, "Empty"),
+            AcType::RunAction => write!(f, "RunAction"),
+            AcType::RunMethod => write!(f, "RunMethod"),
+            AcType::SendMsg => write!(f, "SendMsg"),
+            AcType::Invoke => write!(f, "Invoke"),
+            AcType::Print => write!(f, "Print"),
+            AcType::Goto => write!(f, "Goto"),
+            AcType::CallEngine => write!(f, "CallEngine"),
+            AcType::Other(ac_type) => write!(f, "Other({})", ac_type),
+        }
+    }
+}
+
+// Action type is encoded in debot abi as "0x"-prefixed hex string.
+impl Serialize for AcType {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        to_0x_hex(&u8::from(*self), ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for AcType {
+    fn deserialize<D>(des: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        from_0x_hex(des).map(AcType::from)
+    }
+}
+
+/// Debot action.
+///
+/// Serialized in the same tuple format as debot returns it from `fetch`:
+/// strings as hex-encoded utf8 bytes, numbers as "0x"-prefixed hex and `misc`
+/// as base64 cell. Attributes are kept as raw string, so malformed attributes
+/// do not prevent debot from loading; they are parsed leniently once,
+/// when action is deserialized or its attributes are set.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", from = "ActionTuple")]
 pub struct DAction {
-    #[serde(deserialize_with = "from_hex_to_utf8_str")]
+    #[serde(serialize_with = "to_hex_str")]
     pub desc: String,
-    #[serde(deserialize_with = "from_hex_to_utf8_str")]
+    #[serde(serialize_with = "to_hex_str")]
     pub name: String,
-    #[serde(deserialize_with = "str_to_actype")]
     pub action_type: AcType,
-    #[serde(deserialize_with = "from_0x_hex")]
+    #[serde(serialize_with = "to_0x_hex")]
     pub to: u8,
-    #[serde(deserialize_with = "from_hex_to_utf8_str")]
-    pub attrs: String,
+    #[serde(serialize_with = "to_hex_str")]
+    attrs: String,
     pub misc: String,
+    #[serde(skip)]
+    parsed_attrs: ActionAttrs,
+}
+
+/// Action as it is returned by debot `fetch` function.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ActionTuple {
+    #[serde(deserialize_with = "from_hex_to_utf8_str")]
+    desc: String,
+    #[serde(deserialize_with = "from_hex_to_utf8_str")]
+    name: String,
+    action_type: AcType,
+    #[serde(deserialize_with = "from_0x_hex")]
+    to: u8,
+    #[serde(deserialize_with = "from_hex_to_utf8_str")]
+    attrs: String,
+    misc: String,
+}
+
+impl From<ActionTuple> for DAction {
+    fn from(tuple: ActionTuple) -> Self {
+        DAction {
+            desc: tuple.desc,
+            name: tuple.name,
+            action_type: tuple.action_type,
+            to: tuple.to,
+            parsed_attrs: ActionAttrs::parse_lenient(&tuple.attrs),
+            attrs: tuple.attrs,
+            misc: tuple.misc,
+        }
+    }
 }
 
 impl DAction {
@@ -57,6 +152,7 @@ impl DAction {
             to: 0,
             attrs: String::new(),
             misc: String::new(),
+            parsed_attrs: ActionAttrs::default(),
         }
     }
     
@@ -69,59 +165,65 @@ impl DAction {
             to,
             attrs: String::new(),
             misc: String::new(),
+            parsed_attrs: ActionAttrs::default(),
         }
     }
 
+    /// Raw attributes string as it is stored in debot.
+    pub fn attrs(&self) -> &str {
+        &self.attrs
+    }
+
+    /// Replaces attributes string and parses it leniently.
+    pub fn set_attrs(&mut self, attrs: String) {
+        self.parsed_attrs = ActionAttrs::parse_lenient(&attrs);
+        self.attrs = attrs;
+    }
+
     pub fn is_engine_call(&self) -> bool {
-        match self.action_type {
-            AcType::CallEngine => true,
-            _ => false,
-        }
+        self.action_type == AcType::CallEngine
+    }
+
+    /// Parses attributes strictly, returns error if attributes string is malformed.
+    pub fn parse_attrs(&self) -> Result<ActionAttrs, String> {
+        self.attrs.parse()
     }
 
     pub fn is_instant(&self) -> bool {
-        self.attrs.split(',')
-            .find(|val| val.to_owned() == "instant")
-            .map(|_| true)
-            .unwrap_or(false)
+        self.parsed_attrs.instant
     }
 
     pub fn func_attr(&self) -> Option<String> {
-        self.attr_value("func")
+        self.parsed_attrs.func.clone()
     }
 
     pub fn args_attr(&self) -> Option<String> {
-        self.attr_value("args")
+        self.parsed_attrs.args.clone()
     }
 
     pub fn sign_by_user(&self) -> bool {
-        self.attr_value("sign")
-            .map(|s| s == "by_user")
-            .unwrap_or(false)
+        self.parsed_attrs.sign_by_user()
     }
 
     pub fn format_args(&self) -> Option<String> {
-        self.attr_value("fargs")
-    }
-
-    fn attr_value(&self, name: &str) -> Option<String> {
-        let name = name.to_owned() + "=";
-        self.attrs.split(',')
-            .find(|val| val.starts_with(&name))
-            .map(|val| { 
-                let vec: Vec<&str> = val.split('=').collect();
-                vec[1].to_owned()
-            })
+        self.parsed_attrs.fargs.clone()
     }
 }
 
-fn str_to_actype<'de, D>(des: D) -> Result<AcType, D::Error> 
-where 
-    D: Deserializer<'de>
-{
-    let s: String = Deserialize::deserialize(des)?;
-    u8::from_str_radix(s.trim_start_matches("0x"), 16)
-        .map_err(de::Error::custom)
-        .map(|t| t.into())
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn test_actype_round_trip() {
+        for raw in 0..=u8::MAX {
+            let ac_type = AcType::from(raw);
+            assert_eq!(u8::from(ac_type), raw);
+            let json = serde_json::to_value(ac_type).unwrap();
+            assert_eq!(json, serde_json::json!(format!("0x{:x}", raw)));
+            assert_eq!(serde_json::from_value::<AcType>(json).unwrap(), ac_type);
+        }
+        assert_eq!(AcType::from(10), AcType::CallEngine);
+        assert_eq!(AcType::from(7), AcType::Other(7));
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// Parsed debot action attributes.
+///
+/// Attributes string is a comma-separated list of `key` flags and
+/// `key=value` pairs. Value can be enclosed in double quotes to contain
+/// `,` characters. Inside value `\` escapes the next character.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ActionAttrs {
+    /// Action is executed by engine automatically when context is entered.
+    pub instant: bool,
+    /// Debot function (or get-method for `RunMethod`) which receives action result.
+    pub func: Option<String>,
+    /// Debot function which returns action arguments.
+    pub args: Option<String>,
+    /// Who signs message sent by action (`by_user`).
+    pub sign: Option<String>,
+    /// Debot function which returns arguments for `Print` format string.
+    pub fargs: Option<String>,
+}
+
+impl ActionAttrs {
+    /// Parses attributes skipping the ones which are unknown, duplicate
+    /// or malformed, so single bad attribute does not break the whole debot.
+    /// Use `FromStr` to find out what is wrong with attributes string.
+    pub fn parse_lenient(s: &str) -> Self {
+        let mut items = vec![];
+        // pairs parsed before syntax error are still used
+        let _ = split_attrs(s, &mut items);
+        let mut attrs = ActionAttrs::default();
+        let mut keys: Vec<String> = vec![];
+        for (key, value) in items {
+            if !keys.contains(&key) {
+                // the first valid occurrence of attribute wins
+                if attrs.set(&key, value).is_ok() {
+                    keys.push(key);
+                }
+            }
+        }
+        attrs
+    }
+
+    fn set(&mut self, key: &str, value: Option<String>) -> Result<(), String> {
+        let slot = match key {
+            "instant" => {
+                if value.is_some() {
+                    return Err("attribute \"instant\" has no value".to_owned());
+                }
+                self.instant = true;
+                return Ok(());
+            },
+            "func" => &mut self.func,
+            "args" => &mut self.args,
+            "sign" => &mut self.sign,
+            "fargs" => &mut self.fargs,
+            _ => return Err(format!("unknown attribute \"{}\"", key)),
+        };
+        *slot = Some(value.ok_or_else(|| format!("attribute \"{}\" requires a value", key))?);
+        Ok(())
+    }
+
+    pub fn sign_by_user(&self) -> bool {
+        self.sign.as_deref() == Some("by_user")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ActionAttrs::default()
+    }
+}
+
+impl FromStr for ActionAttrs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items = vec![];
+        split_attrs(s, &mut items)?;
+        let mut attrs = ActionAttrs::default();
+        let mut keys: Vec<String> = vec![];
+        for (key, value) in items {
+            if keys.contains(&key) {
+                return Err(format!("duplicate attribute \"{}\"", key));
+            }
+            attrs.set(&key, value)?;
+            keys.push(key);
+        }
+        Ok(attrs)
+    }
+}
+
+impl fmt::Display for ActionAttrs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut items = vec![];
+        if self.instant {
+            items.push("instant".to_owned());
+        }
+        let values = [
+            ("func", &self.func),
+            ("args", &self.args),
+            ("sign", &self.sign),
+            ("fargs", &self.fargs),
+        ];
+        for (key, value) in values.iter() {
+            if let Some(value) = value {
+                items.push(format!("{}={}", key, quote_value(value)));
+            }
+        }
+        write!(f, "{}", items.join(","))
+    }
+}
+
+/// Splits attributes string into key-value pairs and pushes them to `attrs`.
+/// On syntax error `attrs` contains pairs parsed before the error.
+fn split_attrs(s: &str, attrs: &mut Vec<(String, Option<String>)>) -> Result<(), String> {
+    let mut chars = s.chars().peekable();
+    while chars.peek().is_some() {
+        let mut key = String::new();
+        while let Some(c) = chars.peek() {
+            if *c == ',' || *c == '=' {
+                break;
+            }
+            key.push(*c);
+            chars.next();
+        }
+        let value = if chars.peek() == Some(&'=') {
+            chars.next();
+            Some(parse_value(&mut chars)?)
+        } else {
+            None
+        };
+        match chars.next() {
+            None | Some(',') => {},
+            Some(c) => return Err(format!("unexpected character '{}' after value of \"{}\"", c, key)),
+        }
+        // skip empty items, e.g. trailing comma
+        if key.is_empty() {
+            if value.is_some() {
+                return Err("attribute without name".to_owned());
+            }
+            continue;
+        }
+        attrs.push((key, value));
+    }
+    Ok(())
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut value = String::new();
+    let quoted = chars.peek() == Some(&'"');
+    if quoted {
+        chars.next();
+    }
+    loop {
+        match chars.peek() {
+            None if quoted => return Err("unterminated quoted value".to_owned()),
+            None => break,
+            Some(',') if !quoted => break,
+            _ => {},
+        }
+        match chars.next() {
+            Some('"') if quoted => break,
+            Some('\\') => value.push(chars.next().ok_or_else(|| "unterminated escape".to_owned())?),
+            Some(c) => value.push(c),
+            None => break,
+        }
+    }
+    Ok(value)
+}
+
+fn quote_value(value: &str) -> String {
+    if !value.is_empty() && !value.contains([',', '"', '\\']) {
+        return value.to_owned();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attrs() {
+        let attrs: ActionAttrs = "instant,func=setValue,args=getArgs,sign=by_user".parse().unwrap();
+        assert_eq!(attrs, ActionAttrs {
+            instant: true,
+            func: Some("setValue".to_owned()),
+            args: Some("getArgs".to_owned()),
+            sign: Some("by_user".to_owned()),
+            fargs: None,
+        });
+        assert!(attrs.sign_by_user());
+        assert!("".parse::<ActionAttrs>().unwrap().is_empty());
+        assert!("instant,".parse::<ActionAttrs>().unwrap().instant);
+    }
+
+    #[test]
+    fn test_parse_quoted_and_escaped_values() {
+        let attrs: ActionAttrs = r#"func="a,b=c",args=x\,y=z,fargs="q\"uote""#.parse().unwrap();
+        assert_eq!(attrs.func.as_deref(), Some("a,b=c"));
+        assert_eq!(attrs.args.as_deref(), Some("x,y=z"));
+        assert_eq!(attrs.fargs.as_deref(), Some("q\"uote"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("instant,instant".parse::<ActionAttrs>().is_err());
+        assert!("func=a,func=b".parse::<ActionAttrs>().is_err());
+        assert!("color=red".parse::<ActionAttrs>().is_err());
+        assert!("func".parse::<ActionAttrs>().is_err());
+        assert!("instant=true".parse::<ActionAttrs>().is_err());
+        assert!(r#"func="unterminated"#.parse::<ActionAttrs>().is_err());
+        assert!(r#"func="a"b"#.parse::<ActionAttrs>().is_err());
+        assert!("=value".parse::<ActionAttrs>().is_err());
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let attrs = ActionAttrs::parse_lenient("color=red,instant,func=a,func=b,sign,args=\"x");
+        assert_eq!(attrs, ActionAttrs {
+            instant: true,
+            func: Some("a".to_owned()),
+            ..ActionAttrs::default()
+        });
+        assert_eq!(ActionAttrs::parse_lenient("func=setValue"), "func=setValue".parse().unwrap());
+    }
+
+    #[test]
+    fn test_attrs_round_trip() {
+        let attrs = ActionAttrs {
+            instant: true,
+            func: Some("a,b=c".to_owned()),
+            args: Some("back\\slash".to_owned()),
+            sign: Some(String::new()),
+            fargs: Some("\"quoted\"".to_owned()),
+        };
+        let s = attrs.to_string();
+        assert_eq!(s.parse::<ActionAttrs>().unwrap(), attrs);
+        assert_eq!("instant,func=setValue".parse::<ActionAttrs>().unwrap().to_string(), "instant,func=setValue");
+    }
+}
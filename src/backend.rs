@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use ton_client_rs::{DecodedMessageBody, EncodedMessage, JsonValue,
+    ResultOfLocalRun, TonAddress, TonClient, TonResult, UnsignedMessage};
+
+/// Blockchain operations required by debot engine and its routines.
+///
+/// `TonClient` is the default implementation. Other implementations
+/// allow to run debots against mocks, recorded fixtures or other SDK versions.
+/// Backend must be `Send` and `Sync` so debot engine and its futures
+/// can be moved between threads.
+///
+/// Backend methods are async and engine awaits them, so implementations
+/// can do network requests without blocking browser executor.
+#[async_trait]
+pub trait DebotBackend: Send + Sync {
+    /// Runs contract function locally on `account` state (or on state
+    /// downloaded from blockchain if `account` is None).
+    async fn run_local(
+        &self,
+        addr: &TonAddress,
+        account: Option<JsonValue>,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+        emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun>;
+
+    /// Runs external message locally on `account` state (or on state
+    /// downloaded from blockchain if `account` is None) and decodes its output.
+    /// Message is not sent to blockchain.
+    async fn run_local_msg(
+        &self,
+        addr: &TonAddress,
+        account: Option<JsonValue>,
+        msg: EncodedMessage,
+        abi: JsonValue,
+        func: &str,
+        emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun>;
+
+    /// Creates unsigned external inbound message to call contract function.
+    async fn create_run_message(
+        &self,
+        addr: &TonAddress,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<EncodedMessage>;
+
+    /// Creates external inbound message to call contract function
+    /// together with data which must be signed to complete it.
+    async fn create_unsigned_run_message(
+        &self,
+        addr: &TonAddress,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<UnsignedMessage>;
+
+    /// Completes message created by `create_unsigned_run_message` with signature.
+    async fn add_sign_to_message(
+        &self,
+        signature: &[u8],
+        public_key: &[u8],
+        message: &[u8],
+    ) -> TonResult<EncodedMessage>;
+
+    /// Sends message to blockchain, waits for transaction and returns decoded output.
+    async fn process_message(
+        &self,
+        msg: EncodedMessage,
+        abi: JsonValue,
+        func: &str,
+    ) -> TonResult<serde_json::Value>;
+
+    /// Decodes body of inbound message using contract abi.
+    async fn decode_input_message_body(
+        &self,
+        abi: JsonValue,
+        body: &[u8],
+        internal: bool,
+    ) -> TonResult<DecodedMessageBody>;
+
+    /// Queries accounts collection and returns requested `result` fields.
+    async fn query_accounts(
+        &self,
+        filter: JsonValue,
+        result: &str,
+    ) -> TonResult<Vec<serde_json::Value>>;
+}
+
+/// `TonClient` methods are synchronous: they block current task until SDK responds.
+#[async_trait]
+impl DebotBackend for TonClient {
+    async fn run_local(
+        &self,
+        addr: &TonAddress,
+        account: Option<JsonValue>,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+        emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        self.contracts.run_local(
+            addr,
+            account,
+            abi,
+            func,
+            None,
+            args,
+            None,
+            None,
+            emulate_real_txn
+        )
+    }
+
+    async fn run_local_msg(
+        &self,
+        addr: &TonAddress,
+        account: Option<JsonValue>,
+        msg: EncodedMessage,
+        abi: JsonValue,
+        func: &str,
+        emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        self.contracts.run_local_msg(
+            addr,
+            account,
+            msg,
+            Some(abi),
+            Some(func),
+            None,
+            emulate_real_txn
+        )
+    }
+
+    async fn create_run_message(
+        &self,
+        addr: &TonAddress,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<EncodedMessage> {
+        self.contracts.create_run_message(addr, abi, func, None, args, None, None)
+    }
+
+    async fn create_unsigned_run_message(
+        &self,
+        addr: &TonAddress,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<UnsignedMessage> {
+        self.contracts.create_unsigned_run_message(addr, abi, func, None, args, None)
+    }
+
+    async fn add_sign_to_message(
+        &self,
+        signature: &[u8],
+        public_key: &[u8],
+        message: &[u8],
+    ) -> TonResult<EncodedMessage> {
+        self.contracts.add_sign_to_message(signature, Some(public_key), message)
+    }
+
+    async fn process_message(
+        &self,
+        msg: EncodedMessage,
+        abi: JsonValue,
+        func: &str,
+    ) -> TonResult<serde_json::Value> {
+        self.contracts.process_message(msg, Some(abi), Some(func), false)
+            .map(|res| res.output)
+    }
+
+    async fn decode_input_message_body(
+        &self,
+        abi: JsonValue,
+        body: &[u8],
+        internal: bool,
+    ) -> TonResult<DecodedMessageBody> {
+        self.contracts.decode_input_message_body(abi, body, internal)
+    }
+
+    async fn query_accounts(
+        &self,
+        filter: JsonValue,
+        result: &str,
+    ) -> TonResult<Vec<serde_json::Value>> {
+        self.queries.accounts.query(filter, result, None, None)
+    }
+}
@@ -1,17 +1,173 @@
 use super::action::DAction;
-use ton_client_rs::{Ed25519KeyPair, TonAddress};
+use async_trait::async_trait;
+use super::signer::Signer;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use ton_client_rs::LocalRunFees;
 
-pub trait BrowserCallbacks {
+/// Debot function argument requested from user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputRequest {
+    /// Argument name. Components of tuple arguments are named `tuple.component`.
+    pub name: String,
+    /// Argument type from debot abi, e.g. `uint128` or `address[]`.
+    pub abi_type: String,
+    /// Format of value expected by engine.
+    pub desc: String,
+    /// Why previous value entered by user was rejected.
+    /// Set when engine asks for the same argument again.
+    pub error: Option<String>,
+}
+
+/// Answer of user to `InputRequest`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputResult {
+    /// User entered value.
+    Value(String),
+    /// User refused to enter value (or input stream is closed).
+    /// Engine aborts the action.
+    Cancelled,
+}
+
+/// Argument of contract function called by debot message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallArg {
+    pub name: String,
+    /// Argument type from contract abi.
+    pub abi_type: String,
+    /// Argument value decoded from message body.
+    pub value: serde_json::Value,
+}
+
+/// Message which debot asks to send, decoded for user confirmation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageCall {
+    /// Destination contract address.
+    pub dest: String,
+    /// Called contract function.
+    pub func: String,
+    pub args: Vec<CallArg>,
+    /// Message deploys contract (state init is attached).
+    pub has_state_init: bool,
+    /// Message is signed by user keys.
+    pub signed_by_user: bool,
+    /// Estimated fees, set if fee estimation is enabled and succeeded.
+    pub fees: Option<MessageFees>,
+}
+
+/// Fees of message estimated by running it locally, in nanotokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageFees {
+    /// Total fees charged from account.
+    pub total: u64,
+    pub gas: u64,
+    pub storage: u64,
+    /// Inbound and outbound message forwarding fees.
+    pub forward: u64,
+}
+
+impl From<&LocalRunFees> for MessageFees {
+    fn from(fees: &LocalRunFees) -> Self {
+        MessageFees {
+            total: fees.total_account_fees,
+            gas: fees.gas_fee,
+            storage: fees.storage_fee,
+            forward: fees.in_msg_fwd_fee + fees.out_msgs_fwd_fee,
+        }
+    }
+}
+
+impl fmt::Display for MessageFees {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{} nanotokens (gas {}, storage {}, forward {})",
+            self.total, self.gas, self.storage, self.forward,
+        )
+    }
+}
+
+pub trait BrowserCallbacks: Send {
+    /// Debot sends text message to user.
+    fn log(&self, msg: String);
+    /// Debot is switched to another context.
+    fn switch(&self, ctx_id: u8);
+    // Dengine calls this callback after `switch` callback for every action in context
+    fn show_action(&self, act: DAction);
+    // Debot engine asks user to enter argument for an action.
+    fn input(&self, request: &InputRequest) -> InputResult;
+
+    /// Debot engine asks for user keys to sign message or data.
+    /// Returns None if user refuses to sign.
+    fn signer(&self) -> Option<Box<dyn Signer>>;
+    /// Debot engine asks user to approve message before sending it.
+    /// If user rejects message, action is aborted.
+    fn confirm_message(&self, msg: &MessageCall) -> bool;
+}
+
+/// Asynchronous version of `BrowserCallbacks`.
+///
+/// Callbacks which wait for user (`input`, `signer`, `confirm_message`)
+/// return `Send` futures, so debot engine futures can be spawned
+/// on multi-threaded async runtime.
+#[async_trait]
+pub trait AsyncBrowserCallbacks: Send + Sync {
     /// Debot sends text message to user.
     fn log(&self, msg: String);
     /// Debot is switched to another context.
     fn switch(&self, ctx_id: u8);
     // Dengine calls this callback after `switch` callback for every action in context
     fn show_action(&self, act: DAction);
-    // Debot engine asks user to enter argument for an action. 
-    fn input(&self, prefix: &str, value: &mut String);
+    // Debot engine asks user to enter argument for an action.
+    async fn input(&self, request: &InputRequest) -> InputResult;
+
+    /// Debot engine asks for user keys to sign message or data.
+    /// Returns None if user refuses to sign.
+    async fn signer(&self) -> Option<Box<dyn Signer>>;
+    /// Debot engine asks user to approve message before sending it.
+    /// If user rejects message, action is aborted.
+    async fn confirm_message(&self, msg: &MessageCall) -> bool;
+}
+
+/// Adapter which allows to use synchronous browser with async engine.
+/// Synchronous browser is locked for every call, so it is not required to be `Sync`.
+pub(crate) struct SyncBrowser {
+    inner: Mutex<Box<dyn BrowserCallbacks>>,
+}
+
+impl SyncBrowser {
+    pub fn new(inner: Box<dyn BrowserCallbacks>) -> Self {
+        SyncBrowser { inner: Mutex::new(inner) }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, Box<dyn BrowserCallbacks>> {
+        // browser which panicked in callback is still usable
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl AsyncBrowserCallbacks for SyncBrowser {
+    fn log(&self, msg: String) {
+        self.inner().log(msg)
+    }
+
+    fn switch(&self, ctx_id: u8) {
+        self.inner().switch(ctx_id)
+    }
+
+    fn show_action(&self, act: DAction) {
+        self.inner().show_action(act)
+    }
+
+    async fn input(&self, request: &InputRequest) -> InputResult {
+        self.inner().input(request)
+    }
 
-    fn load_key(&self, keys: &mut Ed25519KeyPair);
+    async fn signer(&self) -> Option<Box<dyn Signer>> {
+        self.inner().signer()
+    }
 
-    fn invoke_debot(&self, debot: TonAddress, action: DAction) -> Result<(), String>;
+    async fn confirm_message(&self, msg: &MessageCall) -> bool {
+        self.inner().confirm_message(msg)
+    }
 }
@@ -1,5 +1,5 @@
 use super::action::DAction;
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::Display;
 use std::str::FromStr;
 
@@ -8,13 +8,13 @@ pub const STATE_CURRENT: u8 = 253;
 pub const STATE_PREV: u8 = 254; 	
 pub const STATE_EXIT: u8 = 255;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DContext {
-    #[serde(deserialize_with = "from_0x_hex")]
+    #[serde(deserialize_with = "from_0x_hex", serialize_with = "to_0x_hex")]
     pub id: u8,
-    #[serde(deserialize_with = "from_hex_to_utf8_str")]
+    #[serde(deserialize_with = "from_hex_to_utf8_str", serialize_with = "to_hex_str")]
     pub desc: String,
     pub actions: Vec<DAction>,
 }
@@ -55,6 +55,133 @@ where
 {
     let s: String = Deserialize::deserialize(des)?;
     let s = str_hex_to_utf8(&s)
-        .ok_or(format!("failed to convert bytes to utf8 string")).unwrap();
+        .ok_or_else(|| de::Error::custom("failed to convert bytes to utf8 string"))?;
     S::from_str(&s).map_err(de::Error::custom)
-}
\ No newline at end of file
+}
+
+pub(super) fn to_0x_hex<S>(val: &u8, ser: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer
+{
+    ser.serialize_str(&format!("0x{:x}", val))
+}
+
+pub(super) fn to_hex_str<T, S>(val: &T, ser: S) -> Result<S::Ok, S::Error>
+where
+    T: Display,
+    S: Serializer
+{
+    ser.serialize_str(&hex::encode(val.to_string().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attrs::ActionAttrs;
+    use proptest::prelude::*;
+    use serde_json::{json, Value};
+
+    fn attrs_strategy() -> impl Strategy<Value = ActionAttrs> {
+        let value = proptest::option::of(any::<String>());
+        (any::<bool>(), value.clone(), value.clone(), value.clone(), value).prop_map(
+            |(instant, func, args, sign, fargs)| ActionAttrs { instant, func, args, sign, fargs },
+        )
+    }
+
+    // Attributes and their raw string written as debot author could write it:
+    // in any order, with quoted or escaped values and optional trailing comma.
+    fn raw_attrs_strategy() -> impl Strategy<Value = (ActionAttrs, String)> {
+        (attrs_strategy(), proptest::collection::vec(any::<bool>(), 4), any::<bool>())
+            .prop_flat_map(|(attrs, quoted, trailing_comma)| {
+                let mut items = vec![];
+                if attrs.instant {
+                    items.push("instant".to_owned());
+                }
+                let values = [
+                    ("func", &attrs.func),
+                    ("args", &attrs.args),
+                    ("sign", &attrs.sign),
+                    ("fargs", &attrs.fargs),
+                ];
+                for ((key, value), quoted) in values.iter().zip(quoted) {
+                    if let Some(value) = value {
+                        items.push(format!("{}={}", key, encode_value(value, quoted)));
+                    }
+                }
+                (Just(attrs), Just(items).prop_shuffle(), Just(trailing_comma))
+            })
+            .prop_map(|(attrs, items, trailing_comma)| {
+                let mut raw = items.join(",");
+                if trailing_comma {
+                    raw.push(',');
+                }
+                (attrs, raw)
+            })
+    }
+
+    fn encode_value(value: &str, quoted: bool) -> String {
+        let mut encoded = String::new();
+        for c in value.chars() {
+            if c == '"' || c == '\\' || (c == ',' && !quoted) {
+                encoded.push('\\');
+            }
+            encoded.push(c);
+        }
+        if quoted {
+            format!("\"{}\"", encoded)
+        } else {
+            encoded
+        }
+    }
+
+    // Action tuple as it is returned by debot `fetch` function.
+    fn action_tuple() -> impl Strategy<Value = Value> {
+        (
+            any::<String>(),
+            any::<String>(),
+            any::<u8>(),
+            any::<u8>(),
+            raw_attrs_strategy(),
+            proptest::collection::vec(any::<u8>(), 0..64),
+        ).prop_map(|(desc, name, action_type, to, (_, attrs), misc)| json!({
+            "desc": hex::encode(desc.as_bytes()),
+            "name": hex::encode(name.as_bytes()),
+            "actionType": format!("0x{:x}", action_type),
+            "to": format!("0x{:x}", to),
+            "attrs": hex::encode(attrs.as_bytes()),
+            "misc": base64::encode(&misc),
+        }))
+    }
+
+    fn context_tuple() -> impl Strategy<Value = Value> {
+        (any::<u8>(), any::<String>(), proptest::collection::vec(action_tuple(), 0..8))
+            .prop_map(|(id, desc, actions)| json!({
+                "id": format!("0x{:x}", id),
+                "desc": hex::encode(desc.as_bytes()),
+                "actions": actions,
+            }))
+    }
+
+    proptest! {
+        #[test]
+        fn test_raw_attrs_parse((attrs, raw) in raw_attrs_strategy()) {
+            prop_assert_eq!(raw.parse::<ActionAttrs>(), Ok(attrs.clone()));
+            prop_assert_eq!(ActionAttrs::parse_lenient(&raw), attrs);
+        }
+
+        #[test]
+        fn test_action_tuple_round_trip(tuple in action_tuple()) {
+            let action: DAction = serde_json::from_value(tuple.clone()).unwrap();
+            prop_assert_eq!(serde_json::to_value(&action).unwrap(), tuple);
+        }
+
+        #[test]
+        fn test_context_tuple_round_trip(tuple in context_tuple()) {
+            let context: DContext = serde_json::from_value(tuple.clone()).unwrap();
+            let encoded = serde_json::to_value(&context).unwrap();
+            prop_assert_eq!(&encoded, &tuple);
+            let decoded: DContext = serde_json::from_value(encoded).unwrap();
+            prop_assert_eq!(decoded, context);
+        }
+    }
+}
@@ -1,21 +1,25 @@
-use crate::routines;
+use crate::routines::{self, Routine, RoutineRegistry, LIST_ROUTINES, RETURN_RESULT};
 use crate::action::{DAction, AcType};
-use crate::browser::BrowserCallbacks;
+use crate::backend::DebotBackend;
+use crate::browser::{AsyncBrowserCallbacks, BrowserCallbacks, InputRequest, InputResult,
+    MessageCall, MessageFees, SyncBrowser};
 use crate::context::{DContext, str_hex_to_utf8, STATE_EXIT, STATE_ZERO, STATE_CURRENT, STATE_PREV};
 use crate::debot_abi::DEBOT_ABI;
-use ton_client_rs::{EncodedMessage, TonClient, TonError, TonErrorKind, 
-    TonAddress, ResultOfLocalRun, JsonValue, Ed25519KeyPair};
+use crate::error::DEngineError;
+use crate::graph;
+use crate::message::{self, create_message, load_ton_address, pack_state};
+use crate::params;
+use crate::session::DEngineSession;
+use crate::validator::{self, ValidationIssue};
+use crate::signer::{SignPurpose, Signer};
+use ton_client_rs::{EncodedMessage, TonClient, TonError, 
+    TonAddress, ResultOfLocalRun, JsonValue};
+use futures::executor::block_on;
+use futures::future::{BoxFuture, FutureExt};
 use std::collections::VecDeque;
-use std::io::Cursor;
 
-fn create_client(url: &str) -> Result<TonClient, String> {
-    TonClient::new_with_base_url(url)
-        .map_err(|e| format!("failed to create tonclient: {}", e.to_string()))
-}
-
-pub fn load_ton_address(addr: &str) -> Result<TonAddress, String> {
-    TonAddress::from_str(addr)
-        .map_err(|e| format!("failed to parse address: {}", e.to_string()))
+fn create_client(url: &str) -> Result<TonClient, DEngineError> {
+    TonClient::new_with_base_url(url).map_err(|e| e.into())
 }
 
 pub type DState = serde_json::Value;
@@ -23,18 +27,52 @@ pub type DState = serde_json::Value;
 const OPTION_ABI: u8 = 1;
 const OPTION_TARGET_ABI: u8 = 2;
 const OPTION_TARGET_ADDR: u8 = 4;
+/// Max number of contexts kept in navigation history.
+const MAX_HISTORY: usize = 64;
+/// Default max number of chained context switches made by instant actions.
+const DEFAULT_MAX_INSTANT_SWITCHES: usize = 32;
+/// Default max number of nested debot invocations.
+const DEFAULT_MAX_INVOKE_DEPTH: usize = 8;
+
+/// Debot suspended by engine while debot invoked by it is running.
+struct Caller {
+    abi: String,
+    addr: TonAddress,
+    state: DState,
+    state_machine: Vec<DContext>,
+    curr_state: u8,
+    history: Vec<u8>,
+    target_addr: Option<TonAddress>,
+    target_abi: Option<String>,
+    invoke_result: Option<String>,
+    /// Context to switch caller to when invoked debot exits.
+    return_to: u8,
+    /// Caller function which receives result of invoked debot.
+    result_handler: Option<String>,
+}
 
 pub struct DEngine {
     abi: String,
     addr: TonAddress,
-    ton: TonClient,
+    ton: Box<dyn DebotBackend>,
     state: DState,
     state_machine: Vec<DContext>,
     curr_state: u8,
-    prev_state: u8,
+    history: Vec<u8>,
+    max_instant_switches: usize,
     target_addr: Option<TonAddress>,
     target_abi: Option<String>,
-    browser: Box<dyn BrowserCallbacks>,
+    browser: Box<dyn AsyncBrowserCallbacks>,
+    /// Result set by debot with `returnResult` routine.
+    invoke_result: Option<String>,
+    callers: Vec<Caller>,
+    invoking: usize,
+    max_invoke_depth: usize,
+    /// Messages are run locally instead of being sent.
+    dry_run: bool,
+    /// Fees are estimated and shown to user before sending message.
+    estimate_fees: bool,
+    routines: RoutineRegistry,
 }
 
 impl DEngine {
@@ -52,6 +90,24 @@ impl DEngine {
         abi: Option<String>,
         ton: TonClient,
         browser: Box<dyn BrowserCallbacks>
+    ) -> Self {
+        DEngine::new_with_backend(addr, abi, Box::new(ton), browser)
+    }
+
+    pub fn new_with_backend(
+        addr: TonAddress,
+        abi: Option<String>,
+        ton: Box<dyn DebotBackend>,
+        browser: Box<dyn BrowserCallbacks>
+    ) -> Self {
+        DEngine::new_with_async_browser(addr, abi, ton, Box::new(SyncBrowser::new(browser)))
+    }
+
+    pub fn new_with_async_browser(
+        addr: TonAddress,
+        abi: Option<String>,
+        ton: Box<dyn DebotBackend>,
+        browser: Box<dyn AsyncBrowserCallbacks>
     ) -> Self {
         DEngine { 
             abi: abi.unwrap_or(DEBOT_ABI.to_owned()),
@@ -60,46 +116,308 @@ impl DEngine {
             state: json!({}),
             state_machine: vec![],
             curr_state : STATE_EXIT,
-            prev_state : STATE_ZERO,
+            history: vec![],
+            max_instant_switches: DEFAULT_MAX_INSTANT_SWITCHES,
             target_addr: None,
             target_abi: None,
             browser,
+            invoke_result: None,
+            callers: vec![],
+            invoking: 0,
+            max_invoke_depth: DEFAULT_MAX_INVOKE_DEPTH,
+            dry_run: false,
+            estimate_fees: false,
+            routines: RoutineRegistry::with_builtins(),
+        }
+    }
+
+    /// Creates engine from saved session. Engine is not refetched,
+    /// call `resume` to show current context to user.
+    pub fn restore(
+        session: DEngineSession,
+        ton: Box<dyn DebotBackend>,
+        browser: Box<dyn BrowserCallbacks>
+    ) -> Result<Self, DEngineError> {
+        DEngine::restore_with_async_browser(session, ton, Box::new(SyncBrowser::new(browser)))
+    }
+
+    pub fn restore_with_async_browser(
+        session: DEngineSession,
+        ton: Box<dyn DebotBackend>,
+        browser: Box<dyn AsyncBrowserCallbacks>
+    ) -> Result<Self, DEngineError> {
+        let addr = load_ton_address(&session.addr)?;
+        let target_addr = session.target_addr
+            .map(|addr| load_ton_address(&addr))
+            .transpose()?;
+        let mut engine = DEngine::new_with_async_browser(addr, Some(session.abi), ton, browser);
+        engine.state = session.state;
+        engine.state_machine = session.state_machine;
+        engine.curr_state = session.curr_state;
+        engine.history = session.history;
+        engine.target_addr = target_addr;
+        engine.target_abi = session.target_abi;
+        Ok(engine)
+    }
+
+    /// Saves debot session which can be restored later by `DEngine::restore`.
+    /// Session of invoked debot cannot be saved: suspended callers are not
+    /// part of the session.
+    pub fn snapshot(&self) -> Result<DEngineSession, DEngineError> {
+        if self.invoke_depth() > 0 {
+            return Err(DEngineError::Session(
+                "cannot save session while invoked debot is running".to_owned(),
+            ));
         }
+        Ok(DEngineSession {
+            addr: self.addr.to_string(),
+            abi: self.abi.clone(),
+            state: self.state.clone(),
+            state_machine: self.state_machine.clone(),
+            curr_state: self.curr_state,
+            history: self.history.clone(),
+            target_addr: self.target_addr.as_ref().map(|addr| addr.to_string()),
+            target_abi: self.target_abi.clone(),
+        })
+    }
+
+    /// Shows current context of restored session to user.
+    /// Instant actions are not executed again.
+    pub fn resume(&mut self) -> Result<(), DEngineError> {
+        self.show_context()
+    }
+
+    /// Shows current context and its actions to user without executing instant actions.
+    fn show_context(&self) -> Result<(), DEngineError> {
+        if self.curr_state == STATE_EXIT {
+            self.browser.switch(STATE_EXIT);
+            return Ok(());
+        }
+        let ctx = self.state_machine.iter()
+            .find(|ctx| ctx.id == self.curr_state)
+            .ok_or(DEngineError::ContextNotFound(self.curr_state))?;
+        self.browser.switch(ctx.id);
+        self.browser.log(ctx.desc.clone());
+        for act in ctx.actions.iter().filter(|a| !a.is_instant() && !a.is_engine_call()) {
+            self.browser.show_action(act.clone());
+        }
+        Ok(())
     }
 
-    pub fn fetch(&mut self) -> Result<(), String> {
-        self.state_machine = self.fetch_state()?;
-        self.prev_state = STATE_EXIT;
+    pub fn fetch(&mut self) -> Result<(), DEngineError> {
+        block_on(self.fetch_async())
+    }
+
+    pub async fn fetch_async(&mut self) -> Result<(), DEngineError> {
+        self.state_machine = self.fetch_state().await?;
+        self.history.clear();
         Ok(())
     }
 
-    fn fetch_state(&mut self) -> Result<Vec<DContext>, String> {
-        self.load_state()?;
-        let mut result = self.run_get("fetch")?;
+    async fn fetch_state(&mut self) -> Result<Vec<DContext>, DEngineError> {
+        self.load_state().await?;
+        let mut result = self.run_get("fetch").await?;
         let context_vec: Vec<DContext> = serde_json::from_value(result.output["contexts"].take())
-            .unwrap();
+            .map_err(|e| DEngineError::InvalidOutput(format!("fetch: invalid contexts: {}", e)))?;
+        // malformed attributes are parsed leniently, let user know what is ignored
+        for ctx in &context_vec {
+            for act in &ctx.actions {
+                if let Err(e) = act.parse_attrs() {
+                    self.browser.log(format!(
+                        "Warning: context #{}, action {}: invalid attributes \"{}\": {}",
+                        ctx.id, act.name, act.attrs(), e,
+                    ));
+                }
+            }
+        }
         Ok(context_vec)
     }
 
-    pub fn start(&mut self) -> Result<(), String> {
-        self.state_machine = self.fetch_state()?;
+    pub fn start(&mut self) -> Result<(), DEngineError> {
+        block_on(self.start_async())
+    }
 
-        self.switch_state(STATE_ZERO, true)
+    pub async fn start_async(&mut self) -> Result<(), DEngineError> {
+        self.state_machine = self.fetch_state().await?;
+        self.curr_state = STATE_EXIT;
+        self.history.clear();
+        self.switch_state(STATE_ZERO).await
     }
 
-    pub fn execute_action(&mut self, act: &DAction) -> Result<(), String> {
-        self.handle_action(&act)
-            .and_then(|_| self.switch_state(act.to, true))
-            .or_else (|e| {
-                self.browser.log(format!("Action failed: {}. Return to previous state.\n", e));
-                self.switch_state(self.prev_state, false)
-            })
+    pub fn execute_action(&mut self, act: &DAction) -> Result<(), DEngineError> {
+        block_on(self.execute_action_async(act))
+    }
+
+    pub async fn execute_action_async(&mut self, act: &DAction) -> Result<(), DEngineError> {
+        let state_before = self.curr_state;
+        let history_before = self.history.clone();
+        let depth_before = self.callers.len();
+        let result = match self.handle_action(act).await {
+            // invoked debot switches caller to `act.to` when it exits
+            Ok(_) if act.action_type == AcType::Invoke => Ok(()),
+            Ok(_) => self.switch_state(act.to).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                if e == DEngineError::Cancelled {
+                    self.browser.log("Action cancelled.".to_owned());
+                } else {
+                    self.browser.log(format!("Action failed: {}. Return to current context.\n", e));
+                }
+                // if invoked debot has exited, engine is already in caller's
+                // `return_to` context and saved state belongs to invoked debot.
+                if self.callers.len() == depth_before {
+                    self.curr_state = state_before;
+                    self.history = history_before;
+                }
+                self.show_context()
+            },
+        }
+    }
+
+    /// Returns to previous context from navigation history.
+    /// If history is empty then debot exits.
+    pub fn back(&mut self) -> Result<(), DEngineError> {
+        block_on(self.back_async())
+    }
+
+    pub async fn back_async(&mut self) -> Result<(), DEngineError> {
+        self.switch_state(STATE_PREV).await
+    }
+
+    /// Checks fetched debot state machine for broken links, unreachable contexts
+    /// and actions which refer to missing functions or routines.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let routines = self.routines();
+        let routines: Vec<&str> = routines.iter().map(|name| name.as_str()).collect();
+        validator::validate(&self.state_machine, &self.abi, self.target_abi.as_deref(), &routines)
+    }
+
+    /// Renders fetched debot state machine as Graphviz DOT digraph.
+    pub fn to_dot(&self) -> String {
+        graph::to_dot(&self.state_machine)
+    }
+
+    /// Renders fetched debot state machine as Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        graph::to_mermaid(&self.state_machine)
+    }
+
+    /// Sets max number of chained context switches made by instant actions.
+    /// If debot exceeds it, engine stops switching and returns an error.
+    pub fn set_max_instant_switches(&mut self, max: usize) {
+        self.max_instant_switches = max;
+    }
+
+    /// Sets max number of nested debot invocations.
+    pub fn set_max_invoke_depth(&mut self, max: usize) {
+        self.max_invoke_depth = max;
+    }
+
+    /// Enables dry run mode: messages of `SendMsg` actions are not sent to blockchain,
+    /// engine runs them locally on target account state and reports the result to browser.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Enables fee estimation: before asking user to confirm message engine
+    /// runs it locally on target account state and passes estimated fees
+    /// to browser in `MessageCall::fees`. Message signed by user is signed
+    /// before confirmation then, and the same message is sent after approval.
+    pub fn set_estimate_fees(&mut self, estimate: bool) {
+        self.estimate_fees = estimate;
+    }
+
+    /// Registers engine routine which debots can call with `CallEngine` action.
+    /// Routine registered with the same name (including built-in one) is replaced.
+    /// Names of routines handled by engine itself (`returnResult`, `listRoutines`)
+    /// cannot be registered.
+    pub fn register_routine(&mut self, name: &str, routine: Box<dyn Routine>) -> Result<(), DEngineError> {
+        if routines::is_reserved(name) {
+            return Err(DEngineError::ReservedRoutine(name.to_owned()));
+        }
+        self.routines.register(name, routine);
+        Ok(())
+    }
+
+    /// Removes engine routine (including built-in one).
+    /// Returns false if there is no such routine.
+    pub fn unregister_routine(&mut self, name: &str) -> bool {
+        self.routines.unregister(name)
+    }
+
+    /// Replaces all engine routines (including built-in ones) with `routines`.
+    pub fn set_routines(&mut self, routines: RoutineRegistry) -> Result<(), DEngineError> {
+        if let Some(name) = routines.names().into_iter().find(|name| routines::is_reserved(name)) {
+            return Err(DEngineError::ReservedRoutine(name.to_owned()));
+        }
+        self.routines = routines;
+        Ok(())
+    }
+
+    /// Names of all engine routines available to debots in alphabetical order.
+    pub fn routines(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.routines.names().into_iter()
+            .chain(vec![LIST_ROUTINES, RETURN_RESULT])
+            .map(|name| name.to_owned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Number of debots suspended by engine while debots invoked by them are running.
+    /// Zero if engine runs the debot it was created for.
+    pub fn invoke_depth(&self) -> usize {
+        self.callers.len()
+    }
+
+    /// Debot contexts loaded by `fetch` or `start`.
+    pub fn contexts(&self) -> &[DContext] {
+        &self.state_machine
+    }
+
+    /// Id of current debot context.
+    pub fn current_state(&self) -> u8 {
+        self.curr_state
+    }
+
+    /// Current debot context. None if debot is not started or exited.
+    pub fn current_context(&self) -> Option<&DContext> {
+        self.state_machine.iter().find(|ctx| ctx.id == self.curr_state)
+    }
+
+    /// Target contract address defined by debot.
+    pub fn target_addr(&self) -> Option<&TonAddress> {
+        self.target_addr.as_ref()
+    }
+
+    /// Target contract abi defined by debot.
+    pub fn target_abi(&self) -> Option<&str> {
+        self.target_abi.as_deref()
+    }
+
+    /// Debot abi used by engine: either abi provided by debot or
+    /// abi passed to constructor.
+    pub fn abi(&self) -> &str {
+        &self.abi
+    }
+
+    /// Ids of previously visited contexts, the oldest first.
+    pub fn history(&self) -> &[u8] {
+        &self.history
     }
     
-    fn handle_action(
+    async fn handle_action(
         &mut self,
         a: &DAction,
-    ) -> Result<Option<Vec<DAction>>, String> {
+    ) -> Result<Option<Vec<DAction>>, DEngineError> {
         match a.action_type {
             AcType::Empty => {
                 debug!("empty action: {}", a.name);
@@ -107,35 +425,35 @@ impl DEngine {
             },
             AcType::RunAction => {
                 debug!("run_action: {}", a.name);
-                self.run_action(&a)
+                self.run_action(&a).await
             },
             AcType::RunMethod => {
-                debug!("run_getmethod: {}", a.func_attr().unwrap());
+                let getmethod = a.func_attr().ok_or_else(|| DEngineError::InvalidAttribute {
+                    action: a.name.clone(),
+                    attr: "func".to_owned(),
+                })?;
+                debug!("run_getmethod: {}", getmethod);
                 let args: Option<JsonValue> = if let Some(getter) = a.args_attr() {
-                    let res = self.run_debot(&getter, None)?;
+                    let res = self.run_debot(&getter, None).await?;
                     Some(res.into())
                 } else {
                     None
                 };
-                self.run_getmethod(&a.func_attr().unwrap(), args, &a.name)?;
+                self.run_getmethod(&getmethod, args, &a.name).await?;
                 Ok(None)
             },
             AcType::SendMsg => {
                 debug!("sendmsg: {}", a.name);
-                let keys = if a.sign_by_user() {
-                    let mut keys = Ed25519KeyPair::zero();
-                    self.browser.load_key(&mut keys);
-                    Some(keys)
-                } else {
-                    None
-                };
                 let args: Option<JsonValue> = if a.misc != /*empty cell*/"te6ccgEBAQEAAgAAAA==" {
                     Some(json!({ "misc": a.misc }).into())
                 } else {
                     None
                 };
-                let result = self.run_sendmsg(&a.name, args, keys)?;
-                self.browser.log(format!("Transaction succeeded."));
+                let result = self.run_sendmsg(&a.name, args, a.sign_by_user()).await?;
+                // dry run outcome is reported by `emulate_message`
+                if !self.dry_run {
+                    self.browser.log(format!("Transaction succeeded."));
+                }
                 if !result.is_null() {
                     self.browser.log(format!("Result: {}", result));
                 }
@@ -143,12 +461,13 @@ impl DEngine {
             },
             AcType::Invoke => {
                 debug!("invoke debot: run {}", a.name);
-                let invoke_args = self.run_debot(&a.name, None)?;
+                let invoke_args = self.run_debot(&a.name, None).await?;
                 debug!("{}", invoke_args);
-                let debot_addr = load_ton_address(invoke_args["debot"].as_str().unwrap())?;
-                let debot_action: DAction = serde_json::from_value(invoke_args["action"].clone()).unwrap();
+                let debot_addr = load_ton_address(output_str(&invoke_args, &a.name, "debot")?)?;
+                let debot_action: DAction = serde_json::from_value(invoke_args["action"].clone())
+                    .map_err(|e| DEngineError::InvalidOutput(format!("{}: invalid action: {}", a.name, e)))?;
                 debug!("invoke debot: {}, action name: {}", &debot_addr, debot_action.name);
-                self.browser.invoke_debot(debot_addr, debot_action)?;
+                self.invoke_debot(debot_addr, debot_action, a.to, a.func_attr()).await?;
                 Ok(None)
             },
             AcType::Print => {
@@ -159,8 +478,8 @@ impl DEngine {
                     } else {
                         None
                     };
-                    let params = self.run_debot(&args_getter, args)?;
-                    routines::format_string(&a.name, &params)
+                    let params = self.run_debot(&args_getter, args).await?;
+                    routines::format_string(&a.name, &params)?
                 } else {
                     a.name.clone()
                 };
@@ -174,69 +493,110 @@ impl DEngine {
             AcType::CallEngine => {
                 debug!("call engine action: {}", a.name);
                 let args = if let Some(args_getter) = a.args_attr() {
-                    let args = self.run_debot(&args_getter, None)?;
+                    let args = self.run_debot(&args_getter, None).await?;
                     args.to_string()
                 } else {
                     a.desc.clone()
                 };
-                let keys = if a.sign_by_user() {
-                    let mut keys = Ed25519KeyPair::zero();
-                    self.browser.load_key(&mut keys);
-                    Some(keys)
+                if a.name == RETURN_RESULT {
+                    self.invoke_result = Some(args);
+                    return Ok(None);
+                }
+                let signer = if a.sign_by_user() {
+                    Some(self.load_signer().await?)
                 } else {
                     None
-                };                
-                let res = self.call_routine(&a.name, &args, keys)?;
-                let setter = a.func_attr().ok_or("routine callback is not specified".to_owned())?;
-                self.run_debot(&setter, Some(json!({"arg1": res}).into()))?;
+                };
+                let res = if a.name == LIST_ROUTINES {
+                    self.routines().join(",")
+                } else {
+                    self.routines.call(self.ton.as_ref(), &a.name, &args, signer.as_deref()).await?
+                };
+                let setter = a.func_attr().ok_or_else(|| DEngineError::InvalidAttribute {
+                    action: a.name.clone(),
+                    attr: "func".to_owned(),
+                })?;
+                self.run_debot(&setter, Some(setter_args(res).into())).await?;
                 Ok(None)
             },
-            _ => {
-                let err_msg = "unsupported action type".to_owned();
-                self.browser.log(err_msg.clone());
-                Err(err_msg)
+            AcType::Other(_) => {
+                let err = DEngineError::UnsupportedAction(a.name.clone());
+                self.browser.log(err.to_string());
+                Err(err)
             },
         }
     }
 
-    fn switch_state(&mut self, mut state_to: u8, force: bool) -> Result<(), String> {
+    async fn switch_state(&mut self, state_to: u8) -> Result<(), DEngineError> {
         debug!("switching to {}", state_to);
-        if state_to == STATE_CURRENT {
-            state_to = self.curr_state;
-        }
-        if state_to == STATE_PREV {
-            state_to = self.prev_state;
-        }
-        if state_to == STATE_EXIT {
-            self.browser.switch(STATE_EXIT);
-        } else if state_to != self.curr_state || force {        
-            let mut instant_switch = true;
-            self.prev_state = self.curr_state;
-            self.curr_state = state_to;
-            while instant_switch {
-                // TODO: restrict cyclic switches
-                let jump_to_ctx = self.state_machine.iter()
-                    .find(|ctx| ctx.id == state_to)
-                    .map(|ctx| ctx.clone());
-                if let Some(ctx) = jump_to_ctx {
-                    self.browser.switch(state_to);
-                    self.browser.log(ctx.desc.clone());
-                    instant_switch = self.enumerate_actions(ctx)?;
-                    state_to = self.curr_state;
-                } else if state_to == STATE_EXIT {
-                    self.browser.switch(STATE_EXIT);
-                    instant_switch = false;
-                } else {
-                    self.browser.log(format!("Debot context #{} not found. Exit.", state_to));
-                    instant_switch = false;
+        let mut state_to = self.navigate(state_to, true);
+        let mut chain = vec![state_to];
+        loop {
+            if state_to == STATE_EXIT {
+                if let Some(caller) = self.callers.pop() {
+                    debug!("invoked debot exited, return to {}", caller.addr);
+                    let result = self.invoke_result.take();
+                    let return_to = caller.return_to;
+                    let result_handler = caller.result_handler.clone();
+                    self.resume_caller(caller);
+                    state_to = self.navigate(return_to, true);
+                    if let Some(handler) = result_handler {
+                        let result = result.unwrap_or_default();
+                        self.run_debot(&handler, Some(setter_args(result).into())).await?;
+                    }
+                    chain = vec![state_to];
+                    continue;
                 }
-                debug!("instant_switch = {}, state_to = {}", instant_switch, state_to);
+                self.browser.switch(STATE_EXIT);
+                break;
+            }
+            let ctx = self.state_machine.iter()
+                .find(|ctx| ctx.id == state_to)
+                .cloned()
+                .ok_or(DEngineError::ContextNotFound(state_to))?;
+            self.browser.switch(state_to);
+            self.browser.log(ctx.desc.clone());
+            match self.enumerate_actions(ctx).await? {
+                Some(next_state) => {
+                    // context left by instant switch is not recorded in history:
+                    // returning to it would switch forward again
+                    state_to = self.navigate(next_state, false);
+                    chain.push(state_to);
+                    if chain.len() > self.max_instant_switches + 1 {
+                        return Err(DEngineError::InstantSwitchLoop(switch_loop(chain)));
+                    }
+                },
+                None => break,
             }
+            debug!("instant switch to {}", state_to);
         }
         Ok(())
     }
 
-    fn enumerate_actions(&mut self, ctx: DContext) -> Result<bool, String> {
+    /// Resolves special context ids, updates navigation history
+    /// and makes resolved context current. Current context is pushed
+    /// to history only if `record` is set.
+    fn navigate(&mut self, state_to: u8, record: bool) -> u8 {
+        let state_to = match state_to {
+            STATE_CURRENT => self.curr_state,
+            STATE_PREV => self.history.pop().unwrap_or(STATE_EXIT),
+            _ => {
+                if record && state_to != self.curr_state && self.curr_state != STATE_EXIT {
+                    if self.history.len() == MAX_HISTORY {
+                        self.history.remove(0);
+                    }
+                    self.history.push(self.curr_state);
+                }
+                state_to
+            },
+        };
+        self.curr_state = state_to;
+        state_to
+    }
+
+    /// Shows context actions to user and executes instant actions.
+    /// Returns context id if instant action wants to switch context.
+    async fn enumerate_actions(&mut self, ctx: DContext) -> Result<Option<u8>, DEngineError> {
         // find, execute and remove instant action from context.
         // if instant action returns new actions then execute them and insert into context.
         for action in &ctx.actions {
@@ -247,191 +607,320 @@ impl DEngine {
                     if act.desc.len() != 0 {
                         self.browser.log(act.desc.clone());
                     }
-                    self.handle_action(&act)?.and_then(|vec| {
-                        vec.iter().for_each(|a| sub_actions.push_back(a.clone()));
-                        Some(())
-                    });
+                    if let Some(vec) = self.handle_action(&act).await? {
+                        sub_actions.extend(vec);
+                    }
+                    // engine runs invoked debot now or has already returned from it.
+                    if act.action_type == AcType::Invoke {
+                        return Ok(None);
+                    }
                     // if instant action wants to switch context then exit and do switch.
-                    let to = if act.to == STATE_CURRENT {
-                        self.curr_state
-                    } else if act.to == STATE_PREV {
-                        self.prev_state
-                    } else {
-                        act.to
-                    };
-                    if to != self.curr_state {
-                        self.curr_state = act.to;
-                        return Ok(true);
+                    if act.to != STATE_CURRENT && act.to != self.curr_state {
+                        return Ok(Some(act.to));
                     }
                 } else if act.is_engine_call() {
-                    self.handle_action(&act)?;
+                    self.handle_action(&act).await?;
                 } else {
                     self.browser.show_action(act);
                 }
             }
         }
-        Ok(false)
+        Ok(None)
+    }
+
+    /// Suspends current debot and starts debot `addr` with the same browser.
+    /// Invoked debot executes `action` right after start. When it exits,
+    /// engine resumes caller, passes result set by invoked debot with
+    /// `returnResult` routine to `result_handler` (empty string if result
+    /// is not set) and switches caller to `return_to` context.
+    fn invoke_debot(
+        &mut self,
+        addr: TonAddress,
+        action: DAction,
+        return_to: u8,
+        result_handler: Option<String>,
+    ) -> BoxFuture<'_, Result<(), DEngineError>> {
+        async move {
+            // `invoking` counts debots which exit right after start and
+            // return to caller which invokes them again.
+            if self.callers.len() >= self.max_invoke_depth || self.invoking >= self.max_invoke_depth {
+                return Err(DEngineError::InvokeDepthExceeded(self.max_invoke_depth));
+            }
+            let caller = self.suspend_caller(addr, return_to, result_handler);
+            self.callers.push(caller);
+            let depth = self.callers.len();
+            self.invoking += 1;
+            let result = match self.start_async().await {
+                Ok(()) if self.callers.len() == depth => self.execute_action_async(&action).await,
+                res => res,
+            };
+            self.invoking -= 1;
+            if result.is_err() && self.callers.len() == depth {
+                let caller = self.callers.pop().unwrap();
+                self.resume_caller(caller);
+            }
+            result
+        }.boxed()
+    }
+
+    fn suspend_caller(
+        &mut self,
+        addr: TonAddress,
+        return_to: u8,
+        result_handler: Option<String>,
+    ) -> Caller {
+        Caller {
+            abi: std::mem::replace(&mut self.abi, DEBOT_ABI.to_owned()),
+            addr: std::mem::replace(&mut self.addr, addr),
+            state: std::mem::replace(&mut self.state, json!({})),
+            state_machine: std::mem::take(&mut self.state_machine),
+            curr_state: std::mem::replace(&mut self.curr_state, STATE_EXIT),
+            history: std::mem::take(&mut self.history),
+            target_addr: self.target_addr.take(),
+            target_abi: self.target_abi.take(),
+            invoke_result: self.invoke_result.take(),
+            return_to,
+            result_handler,
+        }
     }
 
-    fn run_get(&mut self, name: &str) -> Result<ResultOfLocalRun, String> {
-        let res = self.run(false, name, None, true, false)?;
+    fn resume_caller(&mut self, caller: Caller) {
+        self.abi = caller.abi;
+        self.addr = caller.addr;
+        self.state = caller.state;
+        self.state_machine = caller.state_machine;
+        self.curr_state = caller.curr_state;
+        self.history = caller.history;
+        self.target_addr = caller.target_addr;
+        self.target_abi = caller.target_abi;
+        self.invoke_result = caller.invoke_result;
+    }
+
+    async fn run_get(&mut self, name: &str) -> Result<ResultOfLocalRun, DEngineError> {
+        let res = self.run(false, name, None, true, false).await?;
         Ok(res)
     }
 
-    fn run_debot(&mut self, name: &str, args: Option<JsonValue>) -> Result<serde_json::Value, String> {
+    async fn run_debot(&mut self, name: &str, args: Option<JsonValue>) -> Result<serde_json::Value, DEngineError> {
         debug!("run_debot {}, args: {}", name, if args.is_some() { args.clone().unwrap() } else { json!({}).into() });
-        let res = self.run(false, name, args, true, true)?;
-        self.state = res.account.unwrap();
+        let res = self.run(false, name, args, true, true).await?;
+        self.state = res.account.ok_or_else(|| {
+            DEngineError::InvalidOutput(format!("{}: debot state is missing", name))
+        })?;
         Ok(res.output)
     }
 
-    fn run_action(&mut self, action: &DAction) -> Result<Option<Vec<DAction>>, String> {
-        let args = self.query_action_args(action)?;
+    async fn run_action(&mut self, action: &DAction) -> Result<Option<Vec<DAction>>, DEngineError> {
+        let args = self.query_action_args(action).await?;
 
-        let mut output = self.run_debot(&action.name, args)?;
+        let mut output = self.run_debot(&action.name, args).await?;
 
         let action_vec: Option<Vec<DAction>> = match output.is_null() {
-            false => Some(serde_json::from_value(output["actions"].take()).unwrap()),
+            false => Some(serde_json::from_value(output["actions"].take()).map_err(|e| {
+                DEngineError::InvalidOutput(format!("{}: invalid actions: {}", action.name, e))
+            })?),
             true => None,
         };
         Ok(action_vec)
     }
 
-    fn run_sendmsg(
+    async fn run_sendmsg(
         &mut self,
         name: &str,
         args: Option<JsonValue>,
-        keys: Option<Ed25519KeyPair>,
-    ) -> Result<serde_json::Value, String> {
-        let result = self.run_debot(name, args)?;
-        let dest = result["dest"].as_str().unwrap();
-        let body = result["body"].as_str().unwrap();
+        sign_by_user: bool,
+    ) -> Result<serde_json::Value, DEngineError> {
+        let result = self.run_debot(name, args).await?;
+        let dest = output_str(&result, name, "dest")?;
+        let body = output_str(&result, name, "body")?;
         let state = result["state"].as_str();
 
-        let state = state.map(|val| {
-            base64::decode(val).map_err(|e| format!("cannot decode state: {}", e))
+        let mut state = state.map(|val| {
+            base64::decode(val).map_err(|e| DEngineError::Message(format!("cannot decode state: {}", e)))
         }).transpose()?;
 
-        let call_itself = load_ton_address(dest)? == self.addr;
+        let dest_addr = load_ton_address(dest)?;
+        let call_itself = dest_addr == self.addr;
         let abi: &str = if call_itself {
             &self.abi
         } else {
-            self.target_abi.as_ref().unwrap()
+            self.target_abi.as_ref()
+                .ok_or_else(|| DEngineError::TargetUndefined("abi".to_owned()))?
         };
 
-        let res = self.ton.contracts.decode_input_message_body(
+        let body = base64::decode(body).map_err(|e| {
+            DEngineError::InvalidOutput(format!("{}: cannot decode msg body: {}", name, e))
+        })?;
+        let res = self.ton.decode_input_message_body(
             abi.into(),
-            &base64::decode(body).unwrap(),
+            &body,
             true,
-        ).map_err(|e| format!("failed to decode msg body: {}", e))?;
+        ).await.map_err(|e| DEngineError::AbiDecode(format!("failed to decode msg body: {}", e)))?;
 
         debug!("calling {} at address {}", res.function, dest);
         debug!("args: {}", res.output);
-        self.call_target(dest, abi, &res.function, res.output.into(), keys, state)
+        let signer = if sign_by_user {
+            Some(self.load_signer().await?)
+        } else {
+            None
+        };
+        let has_state_init = state.is_some();
+        // to estimate fees message is built and signed before confirmation:
+        // the same message is emulated and then sent after user approval
+        let (msg, fees) = if self.estimate_fees {
+            let msg = self.build_message(
+                &dest_addr, abi, &res.function, res.output.clone().into(), signer.as_deref(), state.take(),
+            ).await?;
+            let fees = message::message_fees(self.ton.as_ref(), &dest_addr, abi, &res.function, msg.clone()).await;
+            let fees = match fees {
+                Ok(fees) => Some(fees),
+                Err(e) => {
+                    self.browser.log(format!("failed to estimate fees: {}", e));
+                    None
+                },
+            };
+            (Some(msg), fees)
+        } else {
+            (None, None)
+        };
+        let call = MessageCall {
+            dest: dest.to_owned(),
+            func: res.function.clone(),
+            args: params::call_args(abi, &res.function, &res.output),
+            has_state_init,
+            signed_by_user: sign_by_user,
+            fees,
+        };
+        if !self.browser.confirm_message(&call).await {
+            return Err(DEngineError::Cancelled);
+        }
+        let msg = match msg {
+            Some(msg) => msg,
+            None => self.build_message(
+                &dest_addr, abi, &res.function, res.output.into(), signer.as_deref(), state,
+            ).await?,
+        };
+        self.call_target(&dest_addr, abi, &res.function, msg).await
     }
 
-    fn run_getmethod(
+    async fn run_getmethod(
         &mut self,
         getmethod: &str,
         args: Option<JsonValue>,
         result_handler: &str,
-    ) -> Result<serde_json::Value, String> {
-        self.update_options()?;
-        let result = self.run(true, getmethod, args, false, false)?;
-        self.run_debot(result_handler, Some(result.output.into()))
+    ) -> Result<serde_json::Value, DEngineError> {
+        self.update_options().await?;
+        let result = self.run(true, getmethod, args, false, false).await?;
+        self.run_debot(result_handler, Some(result.output.into())).await
     }
 
     #[allow(dead_code)]
-    pub fn version(&mut self) -> Result<String, String> {
-        self.run_get("getVersion").map(|res| res.output.to_string())
-    }
-
-    fn load_state(&mut self) -> Result<String, String> {
-        let result = self.run(false, "getVersion", None, false, true)
-            .map_err(|e| format!("failed to fetch debot state: {}", e))?;
-        let name_hex = result.output["name"]
-            .as_str()
-            .unwrap();
-        let ver_str = result.output["semver"]
-            .as_str()
-            .unwrap()
+    pub fn version(&mut self) -> Result<String, DEngineError> {
+        block_on(self.run_get("getVersion")).map(|res| res.output.to_string())
+    }
+
+    async fn load_state(&mut self) -> Result<String, DEngineError> {
+        let result = self.run(false, "getVersion", None, false, true).await?;
+        let name_hex = output_str(&result.output, "getVersion", "name")?;
+        let ver_str = output_str(&result.output, "getVersion", "semver")?
             .trim_start_matches("0x");
-        let name = str_hex_to_utf8(name_hex).unwrap();
-        let ver = u32::from_str_radix(ver_str, 16).unwrap();
+        let name = str_hex_to_utf8(name_hex).ok_or_else(|| {
+            DEngineError::InvalidOutput("getVersion: name is not a utf8 string".to_owned())
+        })?;
+        let ver = u32::from_str_radix(ver_str, 16).map_err(|e| {
+            DEngineError::InvalidOutput(format!("getVersion: invalid semver: {}", e))
+        })?;
         
-        self.state = result.account.unwrap();
+        self.state = result.account.ok_or_else(|| {
+            DEngineError::InvalidOutput("getVersion: debot state is missing".to_owned())
+        })?;
         self.browser.log(format!("{}, version {}.{}.{}", name, ( ver >> 16) as u8, ( ver >> 8) as u8, ver as u8));
-        self.update_options()?;
+        self.update_options().await?;
         Ok(result.output.to_string())
     }
 
-    fn update_options(&mut self) -> Result<(), String> {
-        let params = self.run_get("getDebotOptions")?;
-        let opt_str = params.output["options"].as_str().unwrap();
+    async fn update_options(&mut self) -> Result<(), DEngineError> {
+        let params = self.run_get("getDebotOptions").await?;
+        let opt_str = output_str(&params.output, "getDebotOptions", "options")?;
         let options = u8::from_str_radix(
             opt_str.trim_start_matches("0x"),
             16,
-        ).unwrap();
+        ).map_err(|e| DEngineError::InvalidOutput(format!("getDebotOptions: invalid options: {}", e)))?;
         if options & OPTION_ABI != 0 {
             self.abi = str_hex_to_utf8(
-                params.output["debotAbi"].as_str().unwrap()
-            ).ok_or("cannot convert hex string to debot abi")?;
+                output_str(&params.output, "getDebotOptions", "debotAbi")?
+            ).ok_or_else(|| DEngineError::AbiDecode("cannot convert hex string to debot abi".to_owned()))?;
         }
         if options & OPTION_TARGET_ABI != 0 {
             self.target_abi = str_hex_to_utf8(
-                params.output["targetAbi"].as_str().unwrap()
+                output_str(&params.output, "getDebotOptions", "targetAbi")?
             );
         }
         if (options & OPTION_TARGET_ADDR) != 0 {
-            let addr = params.output["targetAddr"].as_str().unwrap();
+            let addr = output_str(&params.output, "getDebotOptions", "targetAddr")?;
             self.target_addr = Some(load_ton_address(addr)?);
         }
         Ok(())
     }
 
-    fn query_action_args(&self, act: &DAction) -> Result<Option<JsonValue>, String> {
+    async fn query_action_args(&self, act: &DAction) -> Result<Option<JsonValue>, DEngineError> {
         let args: Option<JsonValue> = if act.misc != /*empty cell*/"te6ccgEBAQEAAgAAAA==" {
             Some(json!({ "misc": act.misc }).into())
         } else {
-            let abi_json: serde_json::Value = serde_json::from_str(&self.abi).unwrap();
-            let functions = abi_json["functions"].as_array().unwrap();
-            let func = functions.iter().find(|f| f["name"].as_str().unwrap() == act.name)
-                .ok_or(format!("action not found"))?;
-            let arguments = func["inputs"].as_array().unwrap();
+            let abi_json: serde_json::Value = serde_json::from_str(&self.abi)
+                .map_err(|e| DEngineError::AbiDecode(format!("debot abi is invalid json: {}", e)))?;
+            let functions = abi_json["functions"].as_array()
+                .ok_or_else(|| DEngineError::AbiDecode("debot abi has no functions".to_owned()))?;
+            let func = functions.iter().find(|f| f["name"].as_str() == Some(&act.name))
+                .ok_or_else(|| DEngineError::AbiDecode(format!("function {} not found in debot abi", act.name)))?;
+            let arguments = func["inputs"].as_array()
+                .ok_or_else(|| DEngineError::AbiDecode(format!("function {} has no inputs", act.name)))?;
+            let params = params::flatten_params(arguments)
+                .map_err(|e| DEngineError::AbiDecode(format!("function {}: {}", act.name, e)))?;
             let mut args_json = json!({});
-            for arg in arguments {
-                let arg_name = arg["name"].as_str().unwrap();
-                let prefix = "".to_owned();
-                let mut value = String::new();
-                self.browser.input(&prefix, &mut value);
-                if arg["type"].as_str().unwrap() == "bytes" {
-                    value = hex::encode(value.as_bytes());
-                }
-                args_json[arg_name] = json!(&value);
+            for param in params {
+                let mut request = InputRequest {
+                    name: param.name(),
+                    abi_type: param.abi_type.clone(),
+                    desc: params::type_hint(&param.abi_type),
+                    error: None,
+                };
+                // ask again until user enters valid value or cancels input
+                let value = loop {
+                    let value = match self.browser.input(&request).await {
+                        InputResult::Value(value) => value,
+                        InputResult::Cancelled => return Err(DEngineError::Cancelled),
+                    };
+                    match params::parse_param(&param.abi_type, &value) {
+                        Ok(value) => break value,
+                        Err(e) => request.error = Some(e),
+                    }
+                };
+                params::set_param(&mut args_json, &param, value);
             }
             Some(args_json.into())
         };
         Ok(args)
     }
 
-    fn get_target(&self) -> Result<(&TonAddress, &String), String> {
-        let addr = self.target_addr.as_ref().ok_or(
-            format!("target address is undefined")
+    fn get_target(&self) -> Result<(&TonAddress, &String), DEngineError> {
+        let addr = self.target_addr.as_ref().ok_or_else(||
+            DEngineError::TargetUndefined("address".to_owned())
         )?;
-        let abi = self.target_abi.as_ref().ok_or(
-            format!("target abi is undefined")
+        let abi = self.target_abi.as_ref().ok_or_else(||
+            DEngineError::TargetUndefined("abi".to_owned())
         )?;
         Ok((addr, abi))
     }
 
-    fn run(
+    async fn run(
         &self,
         is_target: bool,
         func: &str,
         args: Option<JsonValue>,
         with_state: bool,
         emulate_real_txn: bool
-    ) -> Result<ResultOfLocalRun, String> {
+    ) -> Result<ResultOfLocalRun, DEngineError> {
         let (addr, abi) = if is_target {
             self.get_target()?
         } else {
@@ -439,118 +928,147 @@ impl DEngine {
         };
         let abi: &str = abi;
         debug!("running {}, addr {}, state = {}", func, &addr, with_state);
-        self.ton.contracts.run_local(
+        let res = self.ton.run_local(
             addr,
             if with_state { Some(self.state.clone().into()) } else { None },
             abi.into(),
             func,
-            None,
             args.unwrap_or(json!({}).into()),
-            None,
-            None,
             emulate_real_txn
-        )
-        .map_err(|e| {
-            error!("{}", e);
-            self.handle_sdk_err(e)
-        })
+        ).await;
+        match res {
+            Ok(res) => Ok(res),
+            Err(e) => {
+                error!("{}", e);
+                Err(self.handle_sdk_err(e).await)
+            },
+        }
     }
 
-    fn call_target(
+    /// Creates message which calls `func` of contract at `addr`,
+    /// signed by signer if it is set, with state init if `state` is set.
+    async fn build_message(
         &self,
-        dest: &str,
+        addr: &TonAddress,
         abi: &str,
         func: &str,
         args: JsonValue,
-        keys: Option<Ed25519KeyPair>,
+        signer: Option<&dyn Signer>,
         state: Option<Vec<u8>>,
-    ) -> Result<serde_json::Value, String > {
-        let addr = load_ton_address(dest)?;
-
-        let msg = self.ton.contracts.create_run_message(
-            &addr,
-            abi.into(),
-            func,
-            None,
-            args,
-            keys.as_ref(),
-            None,
-        )
-        .map_err(|e| {
-            error!("failed to create message: {}", e);
-            format!("failed to create message")
-        })?;
-
-        let msg = pack_state(msg, state)?;
+    ) -> Result<EncodedMessage, DEngineError> {
+        let purpose = SignPurpose::Message { dest: addr.to_string(), func: func.to_owned() };
+        let msg = create_message(self.ton.as_ref(), addr, abi, func, args, signer.map(|signer| (signer, purpose))).await?;
+        pack_state(msg, state)
+    }
 
+    async fn call_target(
+        &self,
+        addr: &TonAddress,
+        abi: &str,
+        func: &str,
+        msg: EncodedMessage,
+    ) -> Result<serde_json::Value, DEngineError> {
+        if self.dry_run {
+            return self.emulate_message(addr, abi, func, msg).await;
+        }
         self.browser.log(format!("sending message {}", msg.message_id));
-        let res = self.ton.contracts.process_message(msg, Some(abi.into()), Some(func), false)
-            .map_err(|e| {
+        match self.ton.process_message(msg, abi.into(), func).await {
+            Ok(res) => Ok(res),
+            Err(e) => {
                 error!("{}", e);
-                self.handle_sdk_err(e)
-            })
-            .map(|res| res.output)?;
-
-        Ok(res)
+                Err(self.handle_sdk_err(e).await)
+            },
+        }
     }
 
-    fn call_routine(
+    /// Runs message locally on target account state downloaded from blockchain.
+    async fn emulate_message(
         &self,
-        name: &str,
-        args: &str,
-        keypair: Option<Ed25519KeyPair>
-    ) -> Result<String, String> {
-        routines::call_routine(&self.ton, name, args, keypair)
-    }
-
-    fn handle_sdk_err(&self, err: TonError) -> String {
-        match err {
-            TonError(TonErrorKind::InnerSdkError(inn), _) => {
-                if inn.message.contains("Wrong data format") {
-                    // when debot's function argument has invalid format
-                    "invalid parameter".to_owned()
-                } else if inn.code == 3025 {
-                    // when debot function throws an exception
-                    if let Some(err) = inn.data["exit_code"].as_i64() {
+        addr: &TonAddress,
+        abi: &str,
+        func: &str,
+        msg: EncodedMessage,
+    ) -> Result<serde_json::Value, DEngineError> {
+        let msg_id = msg.message_id.clone();
+        let res = match self.ton.run_local_msg(addr, None, msg, abi.into(), func, true).await {
+            Ok(res) => res,
+            Err(e) => {
+                error!("{}", e);
+                let e = self.handle_sdk_err(e).await;
+                if let DEngineError::Sdk { exit_code: Some(code), .. } = &e {
+                    self.browser.log(format!("dry run: {} failed with exit code {}", func, code));
+                }
+                return Err(e);
+            },
+        };
+        self.browser.log(format!("dry run: {} succeeded, message {} was not sent", func, msg_id));
+        if let Some(fees) = &res.fees {
+            self.browser.log(format!("fees: {}", MessageFees::from(fees)));
+        }
+        Ok(res.output)
+    }
+
+    /// Asks browser for signer of user keys.
+    async fn load_signer(&self) -> Result<Box<dyn Signer>, DEngineError> {
+        self.browser.signer().await.ok_or(DEngineError::Cancelled)
+    }
+
+    /// Boxed because `run` awaits it on error.
+    fn handle_sdk_err(&self, err: TonError) -> BoxFuture<'_, DEngineError> {
+        async move {
+            match DEngineError::from(err) {
+                DEngineError::Sdk { code, message, exit_code } => {
+                    let message = if message.contains("Wrong data format") {
+                        // when debot's function argument has invalid format
+                        "invalid parameter".to_owned()
+                    } else if let (3025, Some(err)) = (code, exit_code) {
+                        // when debot function throws an exception
                         self.run(
                             false,
                             "getErrorDescription",
                             Some(json!({"error": err}).into()),
                             true,
                             false,
-                        ).ok().and_then(|res| {
+                        ).await.ok().and_then(|res| {
                             res.output["desc"].as_str()
                                 .and_then(|hex| {
                                     hex::decode(&hex).ok()
                                         .and_then(|vec| String::from_utf8(vec).ok())
                                 })
-                        }).unwrap_or(inn.message)
+                        }).unwrap_or(message)
                     } else {
-                        inn.message
-                    }
-                } else {
-                    
-                    inn.message
-                }
-            },
-            _ => format!("{}", err)
-        }
+                        message
+                    };
+                    DEngineError::Sdk { code, message, exit_code }
+                },
+                err => err,
+            }
+        }.boxed()
+    }
+}
+
+/// Extracts the loop from chain of contexts visited by instant switches.
+/// If there is no loop, the whole chain is returned.
+fn switch_loop(mut chain: Vec<u8>) -> Vec<u8> {
+    let last = chain[chain.len() - 1];
+    if let Some(start) = chain[..chain.len() - 1].iter().rposition(|id| *id == last) {
+        chain.drain(..start);
+    }
+    chain
+}
+
+/// Routine or invoked debot result which is json object is passed to setter
+/// as its arguments (object fields are setter parameters), any other result
+/// is passed as `arg1`.
+fn setter_args(res: String) -> serde_json::Value {
+    match serde_json::from_str(&res) {
+        Ok(args @ serde_json::Value::Object(_)) => args,
+        _ => json!({"arg1": res}),
     }
 }
 
-fn pack_state(mut msg: EncodedMessage, state: Option<Vec<u8>>) -> Result<EncodedMessage, String> {
-    if state.is_some() {
-        let mut buff = Cursor::new(state.unwrap());
-        let image = ton_sdk::ContractImage::from_state_init(&mut buff)
-            .map_err(|e| format!("unable to build contract image: {}", e))?;
-        let state_init = image.state_init();
-        let mut raw_msg = ton_sdk::Contract::deserialize_message(&msg.message_body[..])
-            .map_err(|e| format!("cannot deserialize buffer to msg: {}", e))?;
-        raw_msg.set_state_init(state_init);
-        let (msg_bytes, message_id) = ton_sdk::Contract::serialize_message(&raw_msg)
-            .map_err(|e| format!("cannot serialize msg with state: {}", e))?;
-        msg.message_body = msg_bytes;
-        msg.message_id = message_id.to_string();
-    }
-    Ok(msg)
-}
\ No newline at end of file
+fn output_str<'a>(output: &'a serde_json::Value, func: &str, field: &str) -> Result<&'a str, DEngineError> {
+    output[field].as_str().ok_or_else(|| {
+        DEngineError::InvalidOutput(format!("{}: \"{}\" is missing or not a string", func, field))
+    })
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+use ton_client_rs::{TonError, TonErrorKind};
+
+/// Errors returned by debot engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DEngineError {
+    /// Failed to encode or decode data using debot or target abi.
+    AbiDecode(String),
+    /// Blockchain SDK returned an error. `exit_code` is set when contract
+    /// function throws an exception.
+    Sdk {
+        code: u32,
+        message: String,
+        exit_code: Option<i64>,
+    },
+    /// Debot function returned malformed output.
+    InvalidOutput(String),
+    /// Debot context with such id is not found in debot state machine.
+    ContextNotFound(u8),
+    /// Instant actions switch contexts too many times in a row.
+    /// Contains ids of contexts which form the loop.
+    InstantSwitchLoop(Vec<u8>),
+    /// Debots invoke each other too deeply. Contains max invocation depth.
+    InvokeDepthExceeded(usize),
+    /// Action attribute is missing or has invalid value.
+    InvalidAttribute {
+        action: String,
+        attr: String,
+    },
+    /// Debot action has unsupported type.
+    UnsupportedAction(String),
+    /// Engine routine with such name does not exist.
+    UnknownRoutine(String),
+    /// Engine routine failed.
+    Routine(String),
+    /// Routine name is reserved by debot engine and cannot be registered.
+    ReservedRoutine(String),
+    /// User cancelled input of action arguments, rejected message or refused to sign.
+    Cancelled,
+    /// Signer failed to sign data.
+    Signer(String),
+    /// Address cannot be parsed.
+    InvalidAddress(String),
+    /// Target contract address or abi is not defined by debot.
+    TargetUndefined(String),
+    /// Failed to build external message.
+    Message(String),
+    /// Debot backend failed (e.g. cannot load fixture).
+    Backend(String),
+    /// Debot session cannot be saved or restored.
+    Session(String),
+}
+
+impl fmt::Display for DEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DEngineError::AbiDecode(msg) => write!(f, "abi error: {}", msg),
+            DEngineError::Sdk { message, .. } => write!(f, "{}", message),
+            DEngineError::InvalidOutput(msg) => write!(f, "invalid debot output: {}", msg),
+            DEngineError::ContextNotFound(id) => write!(f, "debot context #{} not found", id),
+            DEngineError::InstantSwitchLoop(ids) => {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                write!(f, "instant actions switch contexts in a loop: {}", ids.join(" -> "))
+            },
+            DEngineError::InvokeDepthExceeded(max) => {
+                write!(f, "debot invocation depth exceeds {}", max)
+            },
+            DEngineError::InvalidAttribute { action, attr } => {
+                write!(f, "action {}: attribute \"{}\" is missing or invalid", action, attr)
+            },
+            DEngineError::UnsupportedAction(name) => write!(f, "unsupported action type: {}", name),
+            DEngineError::UnknownRoutine(name) => write!(f, "unknown engine routine: {}", name),
+            DEngineError::Routine(msg) => write!(f, "{}", msg),
+            DEngineError::ReservedRoutine(name) => write!(f, "routine name {} is reserved by engine", name),
+            DEngineError::Cancelled => write!(f, "cancelled by user"),
+            DEngineError::Signer(msg) => write!(f, "signer error: {}", msg),
+            DEngineError::InvalidAddress(msg) => write!(f, "failed to parse address: {}", msg),
+            DEngineError::TargetUndefined(what) => write!(f, "target {} is undefined", what),
+            DEngineError::Message(msg) => write!(f, "{}", msg),
+            DEngineError::Backend(msg) => write!(f, "backend error: {}", msg),
+            DEngineError::Session(msg) => write!(f, "session error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DEngineError {}
+
+impl From<TonError> for DEngineError {
+    fn from(err: TonError) -> Self {
+        match err {
+            TonError(TonErrorKind::InnerSdkError(inn), _) => DEngineError::Sdk {
+                code: inn.code,
+                exit_code: inn.data["exit_code"].as_i64(),
+                message: inn.message,
+            },
+            _ => DEngineError::Sdk {
+                code: 0,
+                message: format!("{}", err),
+                exit_code: None,
+            },
+        }
+    }
+}
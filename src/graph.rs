@@ -0,0 +1,161 @@
+use crate::action::DAction;
+use crate::context::{DContext, STATE_CURRENT, STATE_EXIT, STATE_PREV};
+
+const EXIT_NODE: &str = "exit";
+const PREV_NODE: &str = "prev";
+
+/// How action edge is drawn.
+enum EdgeStyle {
+    Normal,
+    Instant,
+    Special,
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    label: Vec<String>,
+    style: EdgeStyle,
+}
+
+/// Renders debot state machine as Graphviz DOT digraph.
+pub fn to_dot(contexts: &[DContext]) -> String {
+    let mut dot = String::from("digraph debot {\n    node [shape=box];\n");
+    for ctx in contexts {
+        dot += &format!("    {} [label=\"{}\"];\n", ctx_node(ctx.id), dot_escape(&ctx_label(ctx)));
+    }
+    let edges = edges(contexts);
+    if edges.iter().any(|e| e.to == EXIT_NODE) {
+        dot += &format!("    {} [label=\"exit\", shape=doublecircle];\n", EXIT_NODE);
+    }
+    if edges.iter().any(|e| e.to == PREV_NODE) {
+        dot += &format!("    {} [label=\"previous\", shape=circle];\n", PREV_NODE);
+    }
+    for edge in edges {
+        let label: Vec<String> = edge.label.iter().map(|s| dot_escape(s)).collect();
+        let style = match edge.style {
+            EdgeStyle::Normal => "",
+            EdgeStyle::Instant => ", style=bold",
+            EdgeStyle::Special => ", style=dashed",
+        };
+        dot += &format!(
+            "    {} -> {} [label=\"{}\"{}];\n",
+            edge.from, edge.to, label.join("\\n"), style,
+        );
+    }
+    dot += "}\n";
+    dot
+}
+
+/// Renders debot state machine as Mermaid flowchart.
+pub fn to_mermaid(contexts: &[DContext]) -> String {
+    let mut mmd = String::from("graph TD\n");
+    for ctx in contexts {
+        mmd += &format!("    {}[\"{}\"]\n", ctx_node(ctx.id), mermaid_escape(&ctx_label(ctx)));
+    }
+    let edges = edges(contexts);
+    if edges.iter().any(|e| e.to == EXIT_NODE) {
+        mmd += &format!("    {}((\"exit\"))\n", EXIT_NODE);
+    }
+    if edges.iter().any(|e| e.to == PREV_NODE) {
+        mmd += &format!("    {}((\"previous\"))\n", PREV_NODE);
+    }
+    for edge in edges {
+        let label: Vec<String> = edge.label.iter().map(|s| mermaid_escape(s)).collect();
+        let arrow = match edge.style {
+            EdgeStyle::Normal => "-->",
+            EdgeStyle::Instant => "==>",
+            EdgeStyle::Special => "-.->",
+        };
+        mmd += &format!("    {} {}|\"{}\"| {}\n", edge.from, arrow, label.join("<br/>"), edge.to);
+    }
+    mmd
+}
+
+fn edges(contexts: &[DContext]) -> Vec<Edge> {
+    contexts.iter()
+        .flat_map(|ctx| ctx.actions.iter().map(move |act| edge(ctx.id, act)))
+        .collect()
+}
+
+fn edge(from: u8, act: &DAction) -> Edge {
+    let (to, special) = match act.to {
+        STATE_EXIT => (EXIT_NODE.to_owned(), true),
+        STATE_PREV => (PREV_NODE.to_owned(), true),
+        STATE_CURRENT => (ctx_node(from), true),
+        id => (ctx_node(id), false),
+    };
+    let mut label = vec![act.name.clone(), act.action_type.to_string()];
+    if !act.attrs().is_empty() {
+        label.push(act.attrs().to_owned());
+    }
+    let style = if special {
+        EdgeStyle::Special
+    } else if act.is_instant() {
+        EdgeStyle::Instant
+    } else {
+        EdgeStyle::Normal
+    };
+    Edge { from: ctx_node(from), to, label, style }
+}
+
+fn ctx_node(id: u8) -> String {
+    format!("ctx{}", id)
+}
+
+fn ctx_label(ctx: &DContext) -> String {
+    format!("#{} {}", ctx.id, ctx.desc)
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;").replace('\n', "<br/>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::STATE_ZERO;
+
+    fn contexts() -> Vec<DContext> {
+        let mut instant = DAction::new(String::new(), "init".to_owned(), 1, 1);
+        instant.set_attrs("instant".to_owned());
+        vec![
+            DContext::new("Main \"menu\"".to_owned(), vec![
+                instant,
+                DAction::new(String::new(), "Quit".to_owned(), 0, STATE_EXIT),
+            ], STATE_ZERO),
+            DContext::new("Balance".to_owned(), vec![
+                DAction::new(String::new(), "Refresh".to_owned(), 2, STATE_CURRENT),
+                DAction::new(String::new(), "Back".to_owned(), 0, STATE_PREV),
+            ], 1),
+        ]
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = to_dot(&contexts());
+        assert!(dot.starts_with("digraph debot {\n"));
+        assert!(dot.contains("    ctx0 [label=\"#0 Main \\\"menu\\\"\"];\n"));
+        assert!(dot.contains("    exit [label=\"exit\", shape=doublecircle];\n"));
+        assert!(dot.contains("    ctx0 -> ctx1 [label=\"init\\nRunAction\\ninstant\", style=bold];\n"));
+        assert!(dot.contains("    ctx0 -> exit [label=\"Quit\\nEmpty\", style=dashed];\n"));
+        assert!(dot.contains("    ctx1 -> ctx1 [label=\"Refresh\\nRunMethod\", style=dashed];\n"));
+        assert!(dot.contains("    ctx1 -> prev [label=\"Back\\nEmpty\", style=dashed];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let mmd = to_mermaid(&contexts());
+        assert!(mmd.starts_with("graph TD\n"));
+        assert!(mmd.contains("    ctx0[\"#0 Main #quot;menu#quot;\"]\n"));
+        assert!(mmd.contains("    prev((\"previous\"))\n"));
+        assert!(mmd.contains("    ctx0 ==>|\"init<br/>RunAction<br/>instant\"| ctx1\n"));
+        assert!(mmd.contains("    ctx0 -.->|\"Quit<br/>Empty\"| exit\n"));
+        assert!(mmd.contains("    ctx1 -.->|\"Back<br/>Empty\"| prev\n"));
+    }
+}
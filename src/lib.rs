@@ -2,13 +2,32 @@
 #[macro_use] extern crate log;
 
 mod action;
+mod attrs;
+mod backend;
 mod browser;
 mod context;
 mod debot_abi;
 mod dengine;
+mod error;
+mod graph;
+mod message;
+mod params;
+mod replay;
 mod routines;
+mod session;
+mod signer;
+mod validator;
 
 pub use crate::dengine::DEngine;
-pub use crate::context::{DContext, STATE_EXIT, STATE_ZERO};
-pub use crate::action::DAction;
-pub use crate::browser::BrowserCallbacks;
\ No newline at end of file
+pub use crate::context::{DContext, STATE_CURRENT, STATE_EXIT, STATE_PREV, STATE_ZERO};
+pub use crate::action::{AcType, DAction};
+pub use crate::attrs::ActionAttrs;
+pub use crate::browser::{AsyncBrowserCallbacks, BrowserCallbacks, CallArg, InputRequest,
+    InputResult, MessageCall, MessageFees};
+pub use crate::backend::DebotBackend;
+pub use crate::error::DEngineError;
+pub use crate::session::DEngineSession;
+pub use crate::signer::{KeyPairSigner, SignPurpose, Signer};
+pub use crate::validator::ValidationIssue;
+pub use crate::replay::{RecordingBackend, ReplayBackend};
+pub use crate::routines::{Routine, RoutineRegistry};
\ No newline at end of file
@@ -0,0 +1,97 @@
+use crate::backend::DebotBackend;
+use crate::browser::MessageFees;
+use crate::error::DEngineError;
+use crate::signer::{SignPurpose, Signer};
+use std::io::Cursor;
+use ton_client_rs::{EncodedMessage, JsonValue, TonAddress};
+
+pub fn load_ton_address(addr: &str) -> Result<TonAddress, DEngineError> {
+    TonAddress::from_str(addr)
+        .map_err(|e| DEngineError::InvalidAddress(e.to_string()))
+}
+
+/// Creates external message which calls `func` of contract at `addr`.
+/// Message is signed by signer for given purpose if signer is set.
+pub async fn create_message(
+    ton: &dyn DebotBackend,
+    addr: &TonAddress,
+    abi: &str,
+    func: &str,
+    args: JsonValue,
+    signer: Option<(&dyn Signer, SignPurpose)>,
+) -> Result<EncodedMessage, DEngineError> {
+    let (signer, purpose) = match signer {
+        Some(signer) => signer,
+        None => {
+            return ton.create_run_message(addr, abi.into(), func, args).await
+                .map_err(|e| {
+                    error!("failed to create message: {}", e);
+                    DEngineError::Message("failed to create message".to_owned())
+                });
+        },
+    };
+    let msg = ton.create_unsigned_run_message(addr, abi.into(), func, args).await
+        .map_err(|e| {
+            error!("failed to create message: {}", e);
+            DEngineError::Message("failed to create message".to_owned())
+        })?;
+    let signature = signer.sign(&msg.data_to_sign, &purpose).map_err(DEngineError::Signer)?;
+    let public_key = signer.public_key().map_err(DEngineError::Signer)?;
+    ton.add_sign_to_message(&signature, &public_key, &msg.message).await
+        .map_err(|e| {
+            error!("failed to sign message: {}", e);
+            DEngineError::Message("failed to sign message".to_owned())
+        })
+}
+
+/// Estimates fees of calling `func` of contract at `addr` by running
+/// external message locally on current account state. Message is signed
+/// with `SignPurpose::FeeEstimate` and is never sent.
+pub async fn estimate_fees(
+    ton: &dyn DebotBackend,
+    addr: &TonAddress,
+    abi: &str,
+    func: &str,
+    args: JsonValue,
+    signer: Option<&dyn Signer>,
+    state: Option<Vec<u8>>,
+) -> Result<MessageFees, DEngineError> {
+    let purpose = SignPurpose::FeeEstimate { dest: addr.to_string(), func: func.to_owned() };
+    let msg = create_message(ton, addr, abi, func, args, signer.map(|signer| (signer, purpose))).await?;
+    let msg = pack_state(msg, state)?;
+    message_fees(ton, addr, abi, func, msg).await
+}
+
+/// Runs already built message locally on current account state
+/// and returns fees it would cost. Message is not sent.
+pub async fn message_fees(
+    ton: &dyn DebotBackend,
+    addr: &TonAddress,
+    abi: &str,
+    func: &str,
+    msg: EncodedMessage,
+) -> Result<MessageFees, DEngineError> {
+    let res = ton.run_local_msg(addr, None, msg, abi.into(), func, true).await
+        .map_err(DEngineError::from)?;
+    res.fees
+        .map(|fees| MessageFees::from(&fees))
+        .ok_or_else(|| DEngineError::Message("fees are not estimated".to_owned()))
+}
+
+/// Attaches contract state init to message if `state` is set.
+pub fn pack_state(mut msg: EncodedMessage, state: Option<Vec<u8>>) -> Result<EncodedMessage, DEngineError> {
+    if let Some(state) = state {
+        let mut buff = Cursor::new(state);
+        let image = ton_sdk::ContractImage::from_state_init(&mut buff)
+            .map_err(|e| DEngineError::Message(format!("unable to build contract image: {}", e)))?;
+        let state_init = image.state_init();
+        let mut raw_msg = ton_sdk::Contract::deserialize_message(&msg.message_body[..])
+            .map_err(|e| DEngineError::Message(format!("cannot deserialize buffer to msg: {}", e)))?;
+        raw_msg.set_state_init(state_init);
+        let (msg_bytes, message_id) = ton_sdk::Contract::serialize_message(&raw_msg)
+            .map_err(|e| DEngineError::Message(format!("cannot serialize msg with state: {}", e)))?;
+        msg.message_body = msg_bytes;
+        msg.message_id = message_id.to_string();
+    }
+    Ok(msg)
+}
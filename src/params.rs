@@ -0,0 +1,310 @@
+use crate::browser::CallArg;
+use num_bigint::{BigInt, Sign};
+use num_traits::{Num, One};
+use serde_json::Value;
+use ton_client_rs::TonAddress;
+
+/// Debot function input expanded to scalar parameter: tuples are split
+/// into components, `path` holds names from function input to component.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Param {
+    pub path: Vec<String>,
+    pub abi_type: String,
+}
+
+impl Param {
+    pub fn name(&self) -> String {
+        self.path.join(".")
+    }
+}
+
+/// Expands abi function inputs into list of parameters which can be
+/// requested from user one by one.
+pub(crate) fn flatten_params(inputs: &[Value]) -> Result<Vec<Param>, String> {
+    let mut params = vec![];
+    flatten_into(inputs, &[], &mut params)?;
+    Ok(params)
+}
+
+fn flatten_into(inputs: &[Value], path: &[String], params: &mut Vec<Param>) -> Result<(), String> {
+    for input in inputs {
+        let name = input["name"].as_str()
+            .ok_or_else(|| "input without name".to_owned())?;
+        let abi_type = input["type"].as_str()
+            .ok_or_else(|| format!("input {} without type", name))?;
+        let mut path = path.to_vec();
+        path.push(name.to_owned());
+        if abi_type == "tuple" {
+            let components = input["components"].as_array()
+                .ok_or_else(|| format!("tuple {} without components", name))?;
+            flatten_into(components, &path, params)?;
+        } else {
+            params.push(Param { path, abi_type: abi_type.to_owned() });
+        }
+    }
+    Ok(())
+}
+
+/// Puts parameter value into function arguments object.
+pub(crate) fn set_param(args: &mut Value, param: &Param, value: Value) {
+    let mut obj = args;
+    for key in &param.path {
+        obj = &mut obj[key.as_str()];
+    }
+    *obj = value;
+}
+
+/// Matches decoded function arguments with function inputs from abi.
+/// Arguments which are not found in abi have empty type.
+pub(crate) fn call_args(abi: &str, func: &str, args: &Value) -> Vec<CallArg> {
+    let abi: Value = serde_json::from_str(abi).unwrap_or_default();
+    let inputs = abi["functions"].as_array()
+        .and_then(|functions| functions.iter().find(|f| f["name"].as_str() == Some(func)))
+        .and_then(|f| f["inputs"].as_array());
+    match (inputs, args.as_object()) {
+        (Some(inputs), _) => inputs.iter()
+            .map(|input| {
+                let name = input["name"].as_str().unwrap_or_default();
+                CallArg {
+                    name: name.to_owned(),
+                    abi_type: input["type"].as_str().unwrap_or_default().to_owned(),
+                    value: args[name].clone(),
+                }
+            })
+            .collect(),
+        (None, Some(args)) => args.iter()
+            .map(|(name, value)| CallArg {
+                name: name.clone(),
+                abi_type: String::new(),
+                value: value.clone(),
+            })
+            .collect(),
+        (None, None) => vec![],
+    }
+}
+
+/// Describes format of user input expected for abi type.
+pub(crate) fn type_hint(abi_type: &str) -> String {
+    if let Some(inner) = optional_type(abi_type) {
+        return format!("{}, empty for none", type_hint(inner));
+    }
+    if let Some(item) = array_item_type(abi_type) {
+        return match item_is_scalar(item) {
+            true => format!("comma-separated list or json array of: {}", type_hint(item)),
+            false => "json array".to_owned(),
+        };
+    }
+    if let Some((signed, bits)) = integer_type(abi_type) {
+        let sign = if signed { "signed" } else { "unsigned" };
+        return format!("{} {}-bit integer, decimal or 0x-prefixed hex", sign, bits);
+    }
+    match abi_type {
+        "bool" => "true or false".to_owned(),
+        "address" => "address in workchain:hex format".to_owned(),
+        "bytes" | "string" => "text".to_owned(),
+        "cell" => "base64-encoded cell".to_owned(),
+        _ => "json value".to_owned(),
+    }
+}
+
+/// Converts user input to value accepted by abi encoder.
+pub(crate) fn parse_param(abi_type: &str, value: &str) -> Result<Value, String> {
+    if let Some(inner) = optional_type(abi_type) {
+        return match value.trim().is_empty() {
+            true => Ok(Value::Null),
+            false => parse_param(inner, value),
+        };
+    }
+    if let Some(item) = array_item_type(abi_type) {
+        let value = value.trim();
+        if value.starts_with('[') || !item_is_scalar(item) {
+            return parse_json(value);
+        }
+        if value.is_empty() {
+            return Ok(json!([]));
+        }
+        let items = value.split(',')
+            .map(|item_value| parse_param(item, item_value))
+            .collect::<Result<Vec<Value>, String>>()?;
+        return Ok(Value::Array(items));
+    }
+    if let Some((signed, bits)) = integer_type(abi_type) {
+        return parse_integer(value.trim(), signed, bits);
+    }
+    match abi_type {
+        "bool" => match value.trim() {
+            "true" | "yes" | "y" | "1" => Ok(json!(true)),
+            "false" | "no" | "n" | "0" => Ok(json!(false)),
+            _ => Err("expected true or false".to_owned()),
+        },
+        "address" => TonAddress::from_str(value.trim())
+            .map(|addr| json!(addr.to_string()))
+            .map_err(|e| format!("invalid address: {}", e)),
+        "bytes" => Ok(json!(hex::encode(value.as_bytes()))),
+        "string" => Ok(json!(value)),
+        "cell" => base64::decode(value.trim())
+            .map(|_| json!(value.trim()))
+            .map_err(|e| format!("invalid base64: {}", e)),
+        _ => parse_json(value.trim()),
+    }
+}
+
+fn parse_json(value: &str) -> Result<Value, String> {
+    serde_json::from_str(value).map_err(|e| format!("invalid json: {}", e))
+}
+
+fn optional_type(abi_type: &str) -> Option<&str> {
+    if abi_type.starts_with("optional(") && abi_type.ends_with(')') {
+        Some(&abi_type["optional(".len()..abi_type.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn array_item_type(abi_type: &str) -> Option<&str> {
+    if abi_type.ends_with(']') {
+        abi_type.rfind('[').map(|pos| &abi_type[..pos])
+    } else {
+        None
+    }
+}
+
+fn item_is_scalar(abi_type: &str) -> bool {
+    integer_type(abi_type).is_some()
+        || ["bool", "address", "bytes", "string", "cell"].contains(&abi_type)
+}
+
+/// Returns signedness and size in bits of integer abi type.
+fn integer_type(abi_type: &str) -> Option<(bool, u64)> {
+    match abi_type {
+        "gram" | "varuint16" => return Some((false, 120)),
+        "varint16" => return Some((true, 120)),
+        "varuint32" => return Some((false, 248)),
+        "varint32" => return Some((true, 248)),
+        _ => {},
+    }
+    let (signed, bits) = if let Some(bits) = abi_type.strip_prefix("uint") {
+        (false, bits)
+    } else if let Some(bits) = abi_type.strip_prefix("int") {
+        (true, bits)
+    } else {
+        return None;
+    };
+    bits.parse::<u64>().ok()
+        .filter(|bits| *bits > 0 && *bits <= 256)
+        .map(|bits| (signed, bits))
+}
+
+fn parse_integer(value: &str, signed: bool, bits: u64) -> Result<Value, String> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let (radix, digits) = match digits.strip_prefix("0x") {
+        Some(hex) => (16, hex),
+        None => (10, digits),
+    };
+    // `from_str_radix` accepts sign, but it is already parsed
+    let number = Some(digits)
+        .filter(|digits| !digits.starts_with('+') && !digits.starts_with('-'))
+        .and_then(|digits| BigInt::from_str_radix(digits, radix).ok())
+        .ok_or_else(|| "expected decimal or 0x-prefixed hex number".to_owned())?;
+    let number = if negative { -number } else { number };
+    let in_range = match (signed, number.sign()) {
+        (false, Sign::Minus) => false,
+        (false, _) => number.bits() <= bits,
+        (true, Sign::Minus) => (-number.clone() - BigInt::one()).bits() < bits,
+        (true, _) => number.bits() < bits,
+    };
+    if !in_range {
+        let kind = if signed { "int" } else { "uint" };
+        return Err(format!("number does not fit into {}{}", kind, bits));
+    }
+    // abi encoder accepts negative numbers in decimal form only
+    Ok(match number.sign() {
+        Sign::Minus => json!(number.to_str_radix(10)),
+        _ => json!(format!("0x{}", number.to_str_radix(16))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_integers() {
+        assert_eq!(parse_param("uint8", "255"), Ok(json!("0xff")));
+        assert_eq!(parse_param("uint32", " 0x1F "), Ok(json!("0x1f")));
+        assert_eq!(parse_param("int8", "-128"), Ok(json!("-128")));
+        assert_eq!(parse_param("int8", "127"), Ok(json!("0x7f")));
+        assert_eq!(parse_param("gram", "1000000000"), Ok(json!("0x3b9aca00")));
+        assert!(parse_param("uint8", "256").is_err());
+        assert!(parse_param("uint8", "-1").is_err());
+        assert!(parse_param("int8", "128").is_err());
+        assert!(parse_param("int8", "-129").is_err());
+        assert!(parse_param("uint64", "ten").is_err());
+        assert!(parse_param("uint64", "--1").is_err());
+        assert!(parse_param("int64", "0x-1").is_err());
+        assert!(parse_param("uint64", "").is_err());
+    }
+
+    #[test]
+    fn test_parse_scalars() {
+        assert_eq!(parse_param("bool", "yes"), Ok(json!(true)));
+        assert_eq!(parse_param("bool", "false"), Ok(json!(false)));
+        assert!(parse_param("bool", "maybe").is_err());
+        assert_eq!(parse_param("bytes", "hi"), Ok(json!("6869")));
+        assert_eq!(parse_param("string", "hi, there"), Ok(json!("hi, there")));
+        assert_eq!(parse_param("cell", "te6ccgEBAQEAAgAAAA=="), Ok(json!("te6ccgEBAQEAAgAAAA==")));
+        assert!(parse_param("cell", "not base64!").is_err());
+        assert!(parse_param("address", "not an address").is_err());
+    }
+
+    #[test]
+    fn test_parse_compound() {
+        assert_eq!(parse_param("uint8[]", "1, 2,0x3"), Ok(json!(["0x1", "0x2", "0x3"])));
+        assert_eq!(parse_param("uint8[]", ""), Ok(json!([])));
+        assert_eq!(parse_param("uint8[]", "[\"0x1\"]"), Ok(json!(["0x1"])));
+        assert!(parse_param("uint8[]", "1,x").is_err());
+        assert_eq!(parse_param("optional(uint8)", ""), Ok(Value::Null));
+        assert_eq!(parse_param("optional(bool)", "1"), Ok(json!(true)));
+        assert_eq!(parse_param("map(uint8,bool)", "{\"1\": true}"), Ok(json!({"1": true})));
+        assert!(parse_param("map(uint8,bool)", "1=true").is_err());
+    }
+
+    #[test]
+    fn test_call_args() {
+        let abi = r#"{"functions": [{"name": "transfer", "inputs": [
+            {"name": "dest", "type": "address"},
+            {"name": "value", "type": "uint128"}
+        ]}]}"#;
+        let args = json!({"dest": "0:00", "value": "0x1"});
+        assert_eq!(call_args(abi, "transfer", &args), vec![
+            CallArg { name: "dest".to_owned(), abi_type: "address".to_owned(), value: json!("0:00") },
+            CallArg { name: "value".to_owned(), abi_type: "uint128".to_owned(), value: json!("0x1") },
+        ]);
+        assert_eq!(call_args(abi, "unknown", &json!({"a": 1})), vec![
+            CallArg { name: "a".to_owned(), abi_type: String::new(), value: json!(1) },
+        ]);
+    }
+
+    #[test]
+    fn test_flatten_params() {
+        let inputs = json!([
+            {"name": "dest", "type": "address"},
+            {"name": "transfer", "type": "tuple", "components": [
+                {"name": "value", "type": "uint128"},
+                {"name": "bounce", "type": "bool"},
+            ]},
+        ]);
+        let params = flatten_params(inputs.as_array().unwrap()).unwrap();
+        let names: Vec<String> = params.iter().map(|p| p.name()).collect();
+        assert_eq!(names, vec!["dest", "transfer.value", "transfer.bounce"]);
+
+        let mut args = json!({});
+        set_param(&mut args, &params[1], json!("0x1"));
+        set_param(&mut args, &params[2], json!(true));
+        assert_eq!(args, json!({"transfer": {"value": "0x1", "bounce": true}}));
+        assert!(flatten_params(json!([{"type": "bool"}]).as_array().unwrap()).is_err());
+    }
+}
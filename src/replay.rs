@@ -0,0 +1,406 @@
+use crate::backend::DebotBackend;
+use async_trait::async_trait;
+use crate::error::DEngineError;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use ton_client_rs::{DecodedMessageBody, EncodedMessage, InnerSdkError, JsonValue,
+    LocalRunFees, ResultOfLocalRun, TonAddress, TonError, TonErrorKind, TonResult, UnsignedMessage};
+
+const OP_RUN_LOCAL: &str = "run_local";
+const OP_RUN_LOCAL_MSG: &str = "run_local_msg";
+const OP_CREATE_MESSAGE: &str = "create_run_message";
+const OP_CREATE_UNSIGNED: &str = "create_unsigned_run_message";
+const OP_ADD_SIGN: &str = "add_sign_to_message";
+const OP_PROCESS_MESSAGE: &str = "process_message";
+const OP_DECODE_BODY: &str = "decode_input_message_body";
+const OP_QUERY_ACCOUNTS: &str = "query_accounts";
+
+/// One request/response pair of debot backend.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    op: String,
+    func: String,
+    #[serde(default)]
+    args: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<serde_json::Value>,
+}
+
+/// Backend which forwards all requests to `inner` backend and saves every
+/// request/response pair into json fixture file.
+pub struct RecordingBackend {
+    inner: Box<dyn DebotBackend>,
+    path: PathBuf,
+    records: Mutex<Vec<Record>>,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: Box<dyn DebotBackend>, path: &Path) -> Self {
+        RecordingBackend {
+            inner,
+            path: path.to_owned(),
+            records: Mutex::new(vec![]),
+        }
+    }
+
+    fn record<T, F>(
+        &self,
+        op: &str,
+        func: &str,
+        args: serde_json::Value,
+        res: TonResult<T>,
+        to_json: F,
+    ) -> TonResult<T>
+    where
+        F: FnOnce(&T) -> serde_json::Value,
+    {
+        let (result, error) = match &res {
+            Ok(val) => (Some(to_json(val)), None),
+            Err(e) => (None, Some(error_to_json(e))),
+        };
+        self.records.lock().unwrap().push(Record {
+            op: op.to_owned(),
+            func: func.to_owned(),
+            args,
+            result,
+            error,
+        });
+        self.save();
+        res
+    }
+
+    fn save(&self) {
+        let fixture = serde_json::to_string_pretty(&*self.records.lock().unwrap())
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&self.path, json).map_err(|e| e.to_string()));
+        if let Err(e) = fixture {
+            error!("failed to save fixture {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl DebotBackend for RecordingBackend {
+    async fn run_local(
+        &self,
+        addr: &TonAddress,
+        account: Option<JsonValue>,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+        emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        let args_json = to_json(&args);
+        let res = self.inner.run_local(addr, account, abi, func, args, emulate_real_txn).await;
+        self.record(OP_RUN_LOCAL, func, args_json, res, |res| json!({
+            "output": res.output,
+            "account": res.account,
+            "fees": res.fees.as_ref().map(fees_to_json),
+        }))
+    }
+
+    async fn run_local_msg(
+        &self,
+        addr: &TonAddress,
+        account: Option<JsonValue>,
+        msg: EncodedMessage,
+        abi: JsonValue,
+        func: &str,
+        emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        let args_json = json!({ "messageId": msg.message_id });
+        let res = self.inner.run_local_msg(addr, account, msg, abi, func, emulate_real_txn).await;
+        self.record(OP_RUN_LOCAL_MSG, func, args_json, res, |res| json!({
+            "output": res.output,
+            "fees": res.fees.as_ref().map(fees_to_json),
+        }))
+    }
+
+    async fn create_run_message(
+        &self,
+        addr: &TonAddress,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<EncodedMessage> {
+        let args_json = to_json(&args);
+        let res = self.inner.create_run_message(addr, abi, func, args).await;
+        self.record(OP_CREATE_MESSAGE, func, args_json, res, |msg| {
+            serde_json::to_value(msg).unwrap_or_default()
+        })
+    }
+
+    async fn create_unsigned_run_message(
+        &self,
+        addr: &TonAddress,
+        abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<UnsignedMessage> {
+        let args_json = to_json(&args);
+        let res = self.inner.create_unsigned_run_message(addr, abi, func, args).await;
+        self.record(OP_CREATE_UNSIGNED, func, args_json, res, |msg| json!({
+            "message": base64::encode(&msg.message),
+            "dataToSign": base64::encode(&msg.data_to_sign),
+            "expire": msg.expire,
+        }))
+    }
+
+    async fn add_sign_to_message(
+        &self,
+        signature: &[u8],
+        public_key: &[u8],
+        message: &[u8],
+    ) -> TonResult<EncodedMessage> {
+        // signature is not saved: it is different for every key
+        let args_json = json!({ "publicKey": hex::encode(public_key), "message": base64::encode(message) });
+        let res = self.inner.add_sign_to_message(signature, public_key, message).await;
+        self.record(OP_ADD_SIGN, "", args_json, res, |msg| {
+            serde_json::to_value(msg).unwrap_or_default()
+        })
+    }
+
+    async fn process_message(
+        &self,
+        msg: EncodedMessage,
+        abi: JsonValue,
+        func: &str,
+    ) -> TonResult<serde_json::Value> {
+        let args_json = json!({ "messageId": msg.message_id });
+        let res = self.inner.process_message(msg, abi, func).await;
+        self.record(OP_PROCESS_MESSAGE, func, args_json, res, |output| output.clone())
+    }
+
+    async fn decode_input_message_body(
+        &self,
+        abi: JsonValue,
+        body: &[u8],
+        internal: bool,
+    ) -> TonResult<DecodedMessageBody> {
+        let args_json = json!({ "body": base64::encode(body), "internal": internal });
+        let res = self.inner.decode_input_message_body(abi, body, internal).await;
+        self.record(OP_DECODE_BODY, "", args_json, res, |res| {
+            json!({ "function": res.function, "output": res.output })
+        })
+    }
+
+    async fn query_accounts(
+        &self,
+        filter: JsonValue,
+        result: &str,
+    ) -> TonResult<Vec<serde_json::Value>> {
+        let args_json = to_json(&filter);
+        let res = self.inner.query_accounts(filter, result).await;
+        self.record(OP_QUERY_ACCOUNTS, result, args_json, res, |accounts| json!(accounts))
+    }
+}
+
+/// Backend which serves responses from json fixture saved by `RecordingBackend`.
+///
+/// Requests must come in the same order and with the same arguments
+/// as they were recorded, otherwise backend returns an error.
+/// Arguments are not checked for records which have no `args`.
+pub struct ReplayBackend {
+    records: Mutex<VecDeque<Record>>,
+}
+
+impl ReplayBackend {
+    pub fn load(path: &Path) -> Result<Self, DEngineError> {
+        let json = std::fs::read_to_string(path).map_err(|e| {
+            DEngineError::Backend(format!("failed to read fixture {}: {}", path.display(), e))
+        })?;
+        let records: VecDeque<Record> = serde_json::from_str(&json).map_err(|e| {
+            DEngineError::Backend(format!("failed to parse fixture {}: {}", path.display(), e))
+        })?;
+        Ok(ReplayBackend { records: Mutex::new(records) })
+    }
+
+    fn next(&self, op: &str, func: &str, args: serde_json::Value) -> TonResult<serde_json::Value> {
+        let record = self.records.lock().unwrap().pop_front()
+            .ok_or_else(|| replay_err(format!("fixture has no record for {} {}", op, func)))?;
+        if record.op != op || record.func != func {
+            return Err(replay_err(format!(
+                "fixture mismatch: expected {} {}, found {} {}",
+                op, func, record.op, record.func,
+            )));
+        }
+        if !record.args.is_null() && record.args != args {
+            return Err(replay_err(format!(
+                "fixture mismatch: {} {} called with {}, recorded with {}",
+                op, func, args, record.args,
+            )));
+        }
+        if let Some(err) = record.error {
+            return Err(error_from_json(err));
+        }
+        Ok(record.result.unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl DebotBackend for ReplayBackend {
+    async fn run_local(
+        &self,
+        _addr: &TonAddress,
+        _account: Option<JsonValue>,
+        _abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+        _emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        let mut res = self.next(OP_RUN_LOCAL, func, to_json(&args))?;
+        let account = match res["account"].take() {
+            serde_json::Value::Null => None,
+            account => Some(account),
+        };
+        let fees = fees_from_json(res["fees"].take())?;
+        Ok(ResultOfLocalRun { output: res["output"].take(), fees, account })
+    }
+
+    async fn run_local_msg(
+        &self,
+        _addr: &TonAddress,
+        _account: Option<JsonValue>,
+        msg: EncodedMessage,
+        _abi: JsonValue,
+        func: &str,
+        _emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        let mut res = self.next(OP_RUN_LOCAL_MSG, func, json!({ "messageId": msg.message_id }))?;
+        let fees = fees_from_json(res["fees"].take())?;
+        Ok(ResultOfLocalRun { output: res["output"].take(), fees, account: None })
+    }
+
+    async fn create_run_message(
+        &self,
+        _addr: &TonAddress,
+        _abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<EncodedMessage> {
+        let res = self.next(OP_CREATE_MESSAGE, func, to_json(&args))?;
+        serde_json::from_value(res)
+            .map_err(|e| replay_err(format!("invalid message in fixture: {}", e)))
+    }
+
+    async fn create_unsigned_run_message(
+        &self,
+        _addr: &TonAddress,
+        _abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+    ) -> TonResult<UnsignedMessage> {
+        let res = self.next(OP_CREATE_UNSIGNED, func, to_json(&args))?;
+        let decode = |field: &str| {
+            res[field].as_str()
+                .and_then(|s| base64::decode(s).ok())
+                .ok_or_else(|| replay_err(format!("invalid unsigned message in fixture: {}", field)))
+        };
+        Ok(UnsignedMessage {
+            message: decode("message")?,
+            data_to_sign: decode("dataToSign")?,
+            expire: res["expire"].as_u64().map(|expire| expire as u32),
+        })
+    }
+
+    async fn add_sign_to_message(
+        &self,
+        _signature: &[u8],
+        public_key: &[u8],
+        message: &[u8],
+    ) -> TonResult<EncodedMessage> {
+        let args = json!({ "publicKey": hex::encode(public_key), "message": base64::encode(message) });
+        let res = self.next(OP_ADD_SIGN, "", args)?;
+        serde_json::from_value(res)
+            .map_err(|e| replay_err(format!("invalid message in fixture: {}", e)))
+    }
+
+    async fn process_message(
+        &self,
+        msg: EncodedMessage,
+        _abi: JsonValue,
+        func: &str,
+    ) -> TonResult<serde_json::Value> {
+        self.next(OP_PROCESS_MESSAGE, func, json!({ "messageId": msg.message_id }))
+    }
+
+    async fn decode_input_message_body(
+        &self,
+        _abi: JsonValue,
+        body: &[u8],
+        internal: bool,
+    ) -> TonResult<DecodedMessageBody> {
+        let args = json!({ "body": base64::encode(body), "internal": internal });
+        let mut res = self.next(OP_DECODE_BODY, "", args)?;
+        let function = res["function"].as_str()
+            .ok_or_else(|| replay_err("invalid decoded body in fixture".to_owned()))?
+            .to_owned();
+        Ok(DecodedMessageBody { function, output: res["output"].take() })
+    }
+
+    async fn query_accounts(
+        &self,
+        filter: JsonValue,
+        result: &str,
+    ) -> TonResult<Vec<serde_json::Value>> {
+        let res = self.next(OP_QUERY_ACCOUNTS, result, to_json(&filter))?;
+        serde_json::from_value(res)
+            .map_err(|e| replay_err(format!("invalid accounts in fixture: {}", e)))
+    }
+}
+
+fn to_json(val: &JsonValue) -> serde_json::Value {
+    let s = val.to_string();
+    serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s))
+}
+
+fn fees_to_json(fees: &LocalRunFees) -> serde_json::Value {
+    json!({
+        "inMsgFwdFee": fees.in_msg_fwd_fee,
+        "storageFee": fees.storage_fee,
+        "gasFee": fees.gas_fee,
+        "outMsgsFwdFee": fees.out_msgs_fwd_fee,
+        "totalAccountFees": fees.total_account_fees,
+        "totalOutput": fees.total_output,
+    })
+}
+
+fn fees_from_json(fees: serde_json::Value) -> TonResult<Option<LocalRunFees>> {
+    if fees.is_null() {
+        return Ok(None);
+    }
+    serde_json::from_value(fees)
+        .map(Some)
+        .map_err(|e| replay_err(format!("invalid fees in fixture: {}", e)))
+}
+
+fn replay_err(msg: String) -> TonError {
+    TonError::from_kind(TonErrorKind::Msg(msg))
+}
+
+fn error_to_json(err: &TonError) -> serde_json::Value {
+    match err {
+        TonError(TonErrorKind::InnerSdkError(inn), _) => json!({
+            "source": inn.source,
+            "code": inn.code,
+            "message": inn.message,
+            "data": inn.data,
+        }),
+        _ => json!({ "message": err.to_string() }),
+    }
+}
+
+fn error_from_json(err: serde_json::Value) -> TonError {
+    if err["code"].is_null() {
+        return replay_err(err["message"].as_str().unwrap_or_default().to_owned());
+    }
+    match serde_json::from_value::<InnerSdkError>(err) {
+        Ok(inn) => TonError::from_kind(TonErrorKind::InnerSdkError(inn)),
+        Err(e) => replay_err(format!("invalid error in fixture: {}", e)),
+    }
+}
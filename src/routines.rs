@@ -1,26 +1,168 @@
+use async_trait::async_trait;
 use chrono::{TimeZone, Local};
-use ton_client_rs::{TonClient, Ed25519KeyPair};
-use ed25519_dalek::{Keypair, Signature};
-use ed25519::signature::Signer;
+use crate::backend::DebotBackend;
+use crate::context::str_hex_to_utf8;
+use crate::error::DEngineError;
+use crate::message::{self, load_ton_address};
+use crate::signer::{SignPurpose, Signer};
+use futures::future::{BoxFuture, FutureExt};
 use num_bigint::BigUint;
 use num_traits::Num;
+use std::collections::BTreeMap;
 
-pub fn call_routine(
-    ton: &TonClient,
-    name: &str,
-    arg: &str,
-    keypair: Option<Ed25519KeyPair>,
-) -> Result<String, String> {
-    match name {
-        "convertTokens" => convert_string_to_tokens(&ton, arg),
-        "getBalance" => get_balance(&ton, arg),
-        "loadBocFromFile" => load_boc_from_file(&ton, arg),
-        "signHash" => sign_hash(arg, keypair.unwrap_or_default()),
-        _ => Err(format!("unknown engine routine: {}", name))?,
+/// Engine routine which sets result returned by invoked debot to its caller.
+/// Handled by debot engine itself, not by routine registry.
+pub const RETURN_RESULT: &str = "returnResult";
+/// Engine routine which returns comma-separated names of all routines
+/// available to debot. Handled by debot engine itself, not by routine registry.
+pub const LIST_ROUTINES: &str = "listRoutines";
+
+/// Engine routine which can be called by debot with `CallEngine` action.
+///
+/// Routine gets blockchain backend, signer of user keys (if action has
+/// `sign=by_user` attribute) and argument returned by action `args` getter
+/// (json string) or action description. Result is passed to action `func` setter:
+/// if result is json object, its fields are setter arguments, otherwise
+/// result is passed as `arg1`.
+///
+/// Closure is a synchronous routine. Routine which queries backend
+/// implements this trait with `#[async_trait]` and awaits backend calls.
+#[async_trait]
+pub trait Routine: Send + Sync {
+    async fn call(
+        &self,
+        ton: &dyn DebotBackend,
+        arg: &str,
+        signer: Option<&dyn Signer>,
+    ) -> Result<String, DEngineError>;
+}
+
+#[async_trait]
+impl<F> Routine for F
+where
+    F: Fn(&dyn DebotBackend, &str, Option<&dyn Signer>) -> Result<String, DEngineError> + Send + Sync,
+{
+    async fn call(
+        &self,
+        ton: &dyn DebotBackend,
+        arg: &str,
+        signer: Option<&dyn Signer>,
+    ) -> Result<String, DEngineError> {
+        self(ton, arg, signer)
+    }
+}
+
+type BackendRoutineFn = for<'a> fn(
+    &'a dyn DebotBackend,
+    &'a str,
+    Option<&'a dyn Signer>,
+) -> BoxFuture<'a, Result<String, DEngineError>>;
+
+/// Built-in routine which awaits blockchain backend.
+struct BackendRoutine(BackendRoutineFn);
+
+#[async_trait]
+impl Routine for BackendRoutine {
+    async fn call(
+        &self,
+        ton: &dyn DebotBackend,
+        arg: &str,
+        signer: Option<&dyn Signer>,
+    ) -> Result<String, DEngineError> {
+        (self.0)(ton, arg, signer).await
+    }
+}
+
+/// Names of routines handled by debot engine itself. They cannot be
+/// registered in engine routine registry.
+pub(crate) fn is_reserved(name: &str) -> bool {
+    name == RETURN_RESULT || name == LIST_ROUTINES
+}
+
+/// Named engine routines available to debots.
+///
+/// `RoutineRegistry::new()` (and `default()`) is empty,
+/// `RoutineRegistry::with_builtins()` contains built-in routines.
+pub struct RoutineRegistry {
+    routines: BTreeMap<String, Box<dyn Routine>>,
+}
+
+impl RoutineRegistry {
+    pub fn new() -> Self {
+        RoutineRegistry { routines: BTreeMap::new() }
+    }
+
+    /// Registry with built-in routines which debot engine provides by default.
+    pub fn with_builtins() -> Self {
+        let mut registry = RoutineRegistry::new();
+        registry.register("convertTokens", Box::new(
+            |ton: &dyn DebotBackend, arg: &str, _: Option<&dyn Signer>| convert_string_to_tokens(ton, arg)
+        ));
+        registry.register("estimateFees", Box::new(BackendRoutine(
+            |ton, arg, signer| estimate_fees(ton, arg, signer).boxed()
+        )));
+        registry.register("getAccountInfo", Box::new(BackendRoutine(
+            |ton, arg, _| get_account_info(ton, arg).boxed()
+        )));
+        registry.register("getAccountState", Box::new(BackendRoutine(
+            |ton, arg, _| get_account_state(ton, arg).boxed()
+        )));
+        registry.register("getBalance", Box::new(BackendRoutine(
+            |ton, arg, _| get_balance(ton, arg).boxed()
+        )));
+        registry.register("loadBocFromFile", Box::new(
+            |ton: &dyn DebotBackend, arg: &str, _: Option<&dyn Signer>| load_boc_from_file(ton, arg)
+        ));
+        registry.register("signHash", Box::new(
+            |_: &dyn DebotBackend, arg: &str, signer: Option<&dyn Signer>| {
+                let signer = signer.ok_or_else(|| {
+                    DEngineError::Routine("signHash requires user keys: set sign=by_user".to_owned())
+                })?;
+                sign_hash(arg, signer)
+            }
+        ));
+        registry
+    }
+
+    /// Adds routine to registry. Routine registered with the same name is replaced.
+    pub fn register(&mut self, name: &str, routine: Box<dyn Routine>) {
+        self.routines.insert(name.to_owned(), routine);
+    }
+
+    /// Removes routine from registry. Returns false if there is no such routine.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.routines.remove(name).is_some()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.routines.contains_key(name)
+    }
+
+    /// Names of registered routines in alphabetical order.
+    pub fn names(&self) -> Vec<&str> {
+        self.routines.keys().map(|name| name.as_str()).collect()
+    }
+
+    pub async fn call(
+        &self,
+        ton: &dyn DebotBackend,
+        name: &str,
+        arg: &str,
+        signer: Option<&dyn Signer>,
+    ) -> Result<String, DEngineError> {
+        let routine = self.routines.get(name)
+            .ok_or_else(|| DEngineError::UnknownRoutine(name.to_owned()))?;
+        routine.call(ton, arg, signer).await
     }
 }
 
-pub fn convert_string_to_tokens(_ton: &TonClient, arg: &str) -> Result<String, String> {
+impl Default for RoutineRegistry {
+    fn default() -> Self {
+        RoutineRegistry::new()
+    }
+}
+
+pub fn convert_string_to_tokens(_ton: &dyn DebotBackend, arg: &str) -> Result<String, DEngineError> {
     let parts: Vec<&str> = arg.split(".").collect();
     if parts.len() >= 1 && parts.len() <= 2 {
         let mut result = String::new();
@@ -28,135 +170,285 @@ pub fn convert_string_to_tokens(_ton: &TonClient, arg: &str) -> Result<String, S
         if parts.len() == 2 {
             let fraction = format!("{:0<9}", parts[1]);
             if fraction.len() != 9 {
-                return Err("invalid fractional part".to_string());
+                return Err(DEngineError::Routine("invalid fractional part".to_string()));
             }
             result += &fraction;
         } else {
             result += "000000000";
         }
         u64::from_str_radix(&result, 10)
-            .map_err(|e| format!("failed to parse amount: {}", e))?;
+            .map_err(|e| DEngineError::Routine(format!("failed to parse amount: {}", e)))?;
         
         return Ok(result);
     }
-    Err("Invalid amout value".to_string())
+    Err(DEngineError::Routine("Invalid amout value".to_string()))
 }
 
-pub fn get_balance(ton: &TonClient, arg: &str) -> Result<String, String> {
-    let arg_json: serde_json::Value =
-        serde_json::from_str(arg).map_err(|e| format!("arguments is invalid json: {}", e))?;
-    let addr = arg_json["addr"].as_str().ok_or(format!("addr not found"))?;
+pub async fn get_balance(ton: &dyn DebotBackend, arg: &str) -> Result<String, DEngineError> {
+    let arg_json: serde_json::Value = serde_json::from_str(arg)
+        .map_err(|e| DEngineError::Routine(format!("arguments is invalid json: {}", e)))?;
+    let addr = arg_json["addr"].as_str()
+        .ok_or_else(|| DEngineError::Routine("addr not found".to_owned()))?;
     let accounts = ton
-        .queries
-        .accounts
-        .query(
+        .query_accounts(
             json!({
                 "id": { "eq": addr }
             })
             .into(),
             "acc_type_name balance",
-            None,
-            None,
         )
-        .map_err(|e| format!("account query failed: {}", e.to_string()))?;
-    let acc = accounts.get(0).ok_or(format!("account not found"))?;
-    Ok(acc["balance"].as_str().unwrap().to_owned())
+        .await
+        .map_err(DEngineError::from)?;
+    let acc = accounts.get(0)
+        .ok_or_else(|| DEngineError::Routine("account not found".to_owned()))?;
+    acc["balance"].as_str()
+        .map(|balance| balance.to_owned())
+        .ok_or_else(|| DEngineError::Routine("account balance not found".to_owned()))
 }
 
-pub(super) fn format_string(fstr: &str, params: &serde_json::Value) -> String {
+/// Estimates fees of calling contract function by running external message
+/// locally on current account state. Returns fees in nanotokens:
+/// `{"total": .., "gas": .., "storage": .., "forward": ..}`.
+///
+/// Argument: `{"addr": address, "abi": hex abi, "func": name, "args": {...}}`.
+/// Message is signed with `SignPurpose::FeeEstimate` if routine is called
+/// with `sign=by_user` attribute.
+pub async fn estimate_fees(
+    ton: &dyn DebotBackend,
+    arg: &str,
+    signer: Option<&dyn Signer>,
+) -> Result<String, DEngineError> {
+    let arg_json: serde_json::Value = serde_json::from_str(arg)
+        .map_err(|e| DEngineError::Routine(format!("arguments is invalid json: {}", e)))?;
+    let field = |name: &str| arg_json[name].as_str()
+        .ok_or_else(|| DEngineError::Routine(format!("{} not found", name)));
+    let addr = load_ton_address(field("addr")?)?;
+    let abi = str_hex_to_utf8(field("abi")?)
+        .ok_or_else(|| DEngineError::Routine("abi is not a hex string".to_owned()))?;
+    let func = field("func")?;
+    let args = match &arg_json["args"] {
+        serde_json::Value::Null => json!({}),
+        args => args.clone(),
+    };
+    let fees = message::estimate_fees(ton, &addr, &abi, func, args.into(), signer, None).await?;
+    Ok(json!({
+        "total": fees.total.to_string(),
+        "gas": fees.gas.to_string(),
+        "storage": fees.storage.to_string(),
+        "forward": fees.forward.to_string(),
+    }).to_string())
+}
+
+/// Account fields returned by `getAccountInfo` if argument has no `fields`.
+const DEFAULT_ACCOUNT_FIELDS: &[&str] = &["acc_type_name", "balance"];
+
+const EMPTY_CELL: &str = "te6ccgEBAQEAAgAAAA==";
+
+/// Queries account fields. Argument: `{"addr": address, "fields": [names]}`,
+/// `fields` can also be a space-separated string.
+///
+/// Returns json object with requested fields and `exists` flag.
+/// If account is not found, every requested field is still returned
+/// with default value (see `missing_field`), so result can be passed
+/// to typed setter.
+pub async fn get_account_info(ton: &dyn DebotBackend, arg: &str) -> Result<String, DEngineError> {
+    let arg_json: serde_json::Value = serde_json::from_str(arg)
+        .map_err(|e| DEngineError::Routine(format!("arguments is invalid json: {}", e)))?;
+    let addr = arg_json["addr"].as_str()
+        .ok_or_else(|| DEngineError::Routine("addr not found".to_owned()))?;
+    let fields = account_fields(&arg_json["fields"])?;
+    let info = query_account(ton, addr, &fields).await?;
+    Ok(info.to_string())
+}
+
+/// Queries account state. Argument: `{"addr": address}`.
+///
+/// Returns account BOC as base64 `cell`, or empty cell if account is not found.
+pub async fn get_account_state(ton: &dyn DebotBackend, arg: &str) -> Result<String, DEngineError> {
+    let arg_json: serde_json::Value = serde_json::from_str(arg)
+        .map_err(|e| DEngineError::Routine(format!("arguments is invalid json: {}", e)))?;
+    let addr = arg_json["addr"].as_str()
+        .ok_or_else(|| DEngineError::Routine("addr not found".to_owned()))?;
+    let state = query_account(ton, addr, &["boc".to_owned()]).await?;
+    state["boc"].as_str()
+        .map(|boc| boc.to_owned())
+        .ok_or_else(|| DEngineError::Routine("account boc is not a string".to_owned()))
+}
+
+fn account_fields(fields: &serde_json::Value) -> Result<Vec<String>, DEngineError> {
+    let fields: Vec<String> = match fields {
+        serde_json::Value::Null => DEFAULT_ACCOUNT_FIELDS.iter().map(|f| f.to_string()).collect(),
+        serde_json::Value::String(fields) => fields.split_whitespace().map(|f| f.to_owned()).collect(),
+        serde_json::Value::Array(fields) => fields.iter()
+            .map(|f| f.as_str().map(|f| f.to_owned()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| DEngineError::Routine("fields must be strings".to_owned()))?,
+        _ => return Err(DEngineError::Routine("fields must be an array or a string".to_owned())),
+    };
+    if fields.is_empty() {
+        return Err(DEngineError::Routine("fields are empty".to_owned()));
+    }
+    // fields are inserted into query as is
+    if let Some(field) = fields.iter().find(|f| !f.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+        return Err(DEngineError::Routine(format!("invalid account field: {}", field)));
+    }
+    Ok(fields)
+}
+
+async fn query_account(
+    ton: &dyn DebotBackend,
+    addr: &str,
+    fields: &[String],
+) -> Result<serde_json::Value, DEngineError> {
+    let accounts = ton
+        .query_accounts(
+            json!({
+                "id": { "eq": addr }
+            })
+            .into(),
+            &fields.join(" "),
+        )
+        .await
+        .map_err(DEngineError::from)?;
+    let acc = accounts.first();
+    let mut info = json!({ "exists": acc.is_some() });
+    for field in fields {
+        info[field] = match acc {
+            Some(acc) => acc[field].clone(),
+            None => missing_field(field),
+        };
+    }
+    Ok(info)
+}
+
+/// Value of account field reported for account which does not exist:
+/// empty cell for cell fields, `NonExist` type and zero for the rest.
+fn missing_field(field: &str) -> serde_json::Value {
+    match field {
+        "boc" | "code" | "data" | "library" => json!(EMPTY_CELL),
+        "acc_type_name" => json!("NonExist"),
+        "acc_type" => json!(3),
+        _ => json!("0x0"),
+    }
+}
+
+pub(super) fn format_string(fstr: &str, params: &serde_json::Value) -> Result<String, DEngineError> {
     let mut str_builder = String::new();
     for (i, s) in fstr.split("{}").enumerate() {
         str_builder += s;
-        str_builder += &format_arg(&params, i);
+        str_builder += &format_arg(&params, i)?;
     }
-    str_builder
+    Ok(str_builder)
 }
 
-pub(super) fn format_arg(params: &serde_json::Value, i: usize) -> String {
+pub(super) fn format_arg(params: &serde_json::Value, i: usize) -> Result<String, DEngineError> {
     let idx = i.to_string();
     if let Some(arg) = params["param".to_owned() + &idx].as_str() {
-        return arg.to_owned();
+        return Ok(arg.to_owned());
     }
     if let Some(arg) = params["str".to_owned() + &idx].as_str() {
-        return String::from_utf8(hex::decode(arg).unwrap_or(vec![])).unwrap_or(String::new());
+        return Ok(String::from_utf8(hex::decode(arg).unwrap_or(vec![])).unwrap_or(String::new()));
     }
     if let Some(arg) = params["number".to_owned() + &idx].as_str() {
-        // TODO: need to use big number instead of u64
         debug!("parsing number{}: {}", idx, arg);
-        return format!(
-            "{}", u64::from_str_radix(arg.get(2..).unwrap(), 16
-        ).unwrap());
+        let number = BigUint::from_str_radix(arg.trim_start_matches("0x"), 16)
+            .map_err(|e| DEngineError::InvalidOutput(format!("number{} is invalid: {}", idx, e)))?;
+        return Ok(number.to_str_radix(10));
     }
     if let Some(arg) = params["utime".to_owned() + &idx].as_str() {
-        let utime = u32::from_str_radix(arg.get(2..).unwrap(), 16).unwrap();
-        return if utime == 0 {
+        let utime = u32::from_str_radix(arg.trim_start_matches("0x"), 16)
+            .map_err(|e| DEngineError::InvalidOutput(format!("utime{} is invalid: {}", idx, e)))?;
+        return Ok(if utime == 0 {
             "undefined".to_owned()
         } else {
             let date = Local.timestamp(utime as i64, 0);
             date.to_rfc2822()
-        };
+        });
     }
-    String::new()
+    Ok(String::new())
 }
 
-pub(super) fn load_boc_from_file(_ton: &TonClient, arg: &str) -> Result<String, String> {
+pub(super) fn load_boc_from_file(_ton: &dyn DebotBackend, arg: &str) -> Result<String, DEngineError> {
     debug!("load boc file {}", arg);
     let boc = std::fs::read(arg)
-        .map_err(|e| format!(r#"failed to read boc file "{}": {}"#, arg, e))?;
+        .map_err(|e| DEngineError::Routine(format!(r#"failed to read boc file "{}": {}"#, arg, e)))?;
         Ok(base64::encode(&boc))
 
 }
 
-fn extract_hash(arg: &str) -> Result<Vec<u8>, String> {
+fn extract_hash(arg: &str) -> Result<Vec<u8>, DEngineError> {
     let arg_json: serde_json::Value = serde_json::from_str(arg)
-        .map_err(|e| format!("argument is invalid json: {}", e))?;
+        .map_err(|e| DEngineError::Routine(format!("argument is invalid json: {}", e)))?;
     let mut hash_str = arg_json["hash"].as_str()
-        .ok_or(format!(r#""hash" argument not found"#))?;
+        .ok_or_else(|| DEngineError::Routine(r#""hash" argument not found"#.to_owned()))?;
     if hash_str.starts_with("0x") {
         hash_str = hash_str.get(2..)
-            .ok_or("hash is not an uint256 number".to_owned())?;
+            .ok_or_else(|| DEngineError::Routine("hash is not an uint256 number".to_owned()))?;
     }
     let hash_int = BigUint::from_str_radix(hash_str, 16)
-        .map_err(|_| "hash is not an uint256 number".to_owned())?;
+        .map_err(|_| DEngineError::Routine("hash is not an uint256 number".to_owned()))?;
     hex::decode(&format!("{:0>64}", hash_int.to_str_radix(16)))
-        .map_err(|e| {
+        .map_err(|e| DEngineError::Routine(
             format!("failed to decode hash from hex string:\n hash: {}\n error: {}", hash_str, e)
-        })
+        ))
 }
 
-pub(super) fn sign_hash(arg: &str, keypair: Ed25519KeyPair) -> Result<String, String> {
+pub(super) fn sign_hash(arg: &str, signer: &dyn Signer) -> Result<String, DEngineError> {
     debug!("sign hash {}", arg);
     let hash_vec = extract_hash(arg)?;
-    let keypair = Keypair::from_bytes(&keypair.to_bytes()).unwrap();
-    let signature: Signature = keypair.sign(&hash_vec);
-    Ok(hex::encode(&signature.to_bytes()[..]))
+    let signature = signer.sign(&hash_vec, &SignPurpose::Hash)
+        .map_err(|e| DEngineError::Routine(format!("failed to sign hash: {}", e)))?;
+    Ok(hex::encode(&signature))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::signer::KeyPairSigner;
     use hex;
+    use ton_client_rs::Ed25519KeyPair;
+
+    #[test]
+    fn test_registry() {
+        assert!(RoutineRegistry::default().names().is_empty());
+        let mut registry = RoutineRegistry::with_builtins();
+        assert_eq!(
+            registry.names(),
+            vec![
+                "convertTokens", "estimateFees", "getAccountInfo", "getAccountState",
+                "getBalance", "loadBocFromFile", "signHash",
+            ],
+        );
+        registry.register("echo", Box::new(
+            |_: &dyn DebotBackend, arg: &str, _: Option<&dyn Signer>| Ok(arg.to_owned())
+        ));
+        assert!(registry.contains("echo"));
+        assert!(registry.unregister("getBalance"));
+        assert!(!registry.unregister("getBalance"));
+        assert!(!registry.contains("getBalance"));
+    }
 
-    fn get_keypair() -> Ed25519KeyPair {
+    fn get_keypair() -> KeyPairSigner {
         let keys_str = r#"{
             "public": "9711a04f0b19474272bc7bae5472a8fbbb6ef71ce9c193f5ec3f5af808069a41",
             "secret": "cdf2a820517fa783b9b6094d15e650af92d485084ab217fc2c859f02d49623f3"
         }"#;
-        serde_json::from_str(&keys_str).unwrap()
+        let keys: Ed25519KeyPair = serde_json::from_str(&keys_str).unwrap();
+        KeyPairSigner::new(&keys).unwrap()
     }
 
     #[test]
     fn test_sign_hash_1() {
         let hash = "0x432461b752243bba76ad56fe14f88d2d0bb224c68f1c598dd3a34ee3204ddc84";
         let arg = json!({ "hash": hash }).to_string();
-        sign_hash(&arg, get_keypair()).unwrap();
+        sign_hash(&arg, &get_keypair()).unwrap();
     }
 
     #[test]
     fn test_sign_hash_2() {
         let hash2 = "0x32461b752243bba76ad56fe14f88d2d0bb224c68f1c598dd3a34ee3204ddc84";
         let arg = json!({ "hash": hash2 }).to_string();
-        sign_hash(&arg, get_keypair()).unwrap();
+        sign_hash(&arg, &get_keypair()).unwrap();
     }
 
     #[test]
@@ -189,4 +481,27 @@ mod tests {
         let arg = json!({ "hash": hash }).to_string();
         assert_eq!(true, extract_hash(&arg).is_err());
     }
+
+    #[test]
+    fn test_account_fields() {
+        assert_eq!(account_fields(&json!(null)).unwrap(), vec!["acc_type_name", "balance"]);
+        assert_eq!(account_fields(&json!("code_hash  last_paid")).unwrap(), vec!["code_hash", "last_paid"]);
+        assert_eq!(account_fields(&json!(["data"])).unwrap(), vec!["data"]);
+        assert!(account_fields(&json!([])).is_err());
+        assert!(account_fields(&json!([1])).is_err());
+        assert!(account_fields(&json!("balance } transactions { id")).is_err());
+    }
+
+    #[test]
+    fn test_format_big_number() {
+        let params = json!({ "number0": "0x3635c9adc5dea00000" });
+        assert_eq!("Value: 1000000000000000000000", format_string("Value: {}", &params).unwrap());
+    }
+
+    #[test]
+    fn test_format_invalid_number() {
+        let params = json!({ "number0": "0xqwerty", "utime1": "qwerty" });
+        assert_eq!(true, format_arg(&params, 0).is_err());
+        assert_eq!(true, format_arg(&params, 1).is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,32 @@
+use crate::context::DContext;
+use crate::error::DEngineError;
+use serde::{Deserialize, Serialize};
+
+/// Serializable snapshot of debot session.
+///
+/// Contains everything needed to resume debot in the same context
+/// with the same local debot state: no refetch or action replay is required.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DEngineSession {
+    pub(crate) addr: String,
+    pub(crate) abi: String,
+    pub(crate) state: serde_json::Value,
+    pub(crate) state_machine: Vec<DContext>,
+    pub(crate) curr_state: u8,
+    pub(crate) history: Vec<u8>,
+    pub(crate) target_addr: Option<String>,
+    pub(crate) target_abi: Option<String>,
+}
+
+impl DEngineSession {
+    pub fn to_json(&self) -> Result<String, DEngineError> {
+        serde_json::to_string(self)
+            .map_err(|e| DEngineError::Session(format!("failed to serialize session: {}", e)))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, DEngineError> {
+        serde_json::from_str(json)
+            .map_err(|e| DEngineError::Session(format!("failed to parse session: {}", e)))
+    }
+}
@@ -0,0 +1,49 @@
+use ed25519::signature::Signer as _;
+use ed25519_dalek::Keypair;
+use ton_client_rs::Ed25519KeyPair;
+
+/// What debot asks user to sign.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignPurpose {
+    /// External message which calls function `func` of contract `dest`.
+    Message { dest: String, func: String },
+    /// Message which is only run locally to estimate fees and is never sent.
+    FeeEstimate { dest: String, func: String },
+    /// Hash passed by debot to `signHash` routine.
+    Hash,
+}
+
+/// Signs data on behalf of user.
+///
+/// Debot engine never gets secret key: it passes data to sign to signer,
+/// so keys can be kept in hardware wallet, OS keychain or remote service.
+/// Signer must be `Sync`: engine futures hold it across backend calls.
+pub trait Signer: Send + Sync {
+    /// Ed25519 public key (32 bytes) of signing key.
+    fn public_key(&self) -> Result<Vec<u8>, String>;
+    /// Returns ed25519 signature (64 bytes) of `data`.
+    fn sign(&self, data: &[u8], purpose: &SignPurpose) -> Result<Vec<u8>, String>;
+}
+
+/// Signer which keeps key pair in memory.
+pub struct KeyPairSigner {
+    keypair: Keypair,
+}
+
+impl KeyPairSigner {
+    pub fn new(keys: &Ed25519KeyPair) -> Result<Self, String> {
+        let keypair = Keypair::from_bytes(&keys.to_bytes())
+            .map_err(|e| format!("invalid keypair: {}", e))?;
+        Ok(KeyPairSigner { keypair })
+    }
+}
+
+impl Signer for KeyPairSigner {
+    fn public_key(&self) -> Result<Vec<u8>, String> {
+        Ok(self.keypair.public.to_bytes().to_vec())
+    }
+
+    fn sign(&self, data: &[u8], _purpose: &SignPurpose) -> Result<Vec<u8>, String> {
+        Ok(self.keypair.sign(data).to_bytes().to_vec())
+    }
+}
@@ -0,0 +1,257 @@
+use crate::action::{AcType, DAction};
+use crate::context::{DContext, STATE_CURRENT, STATE_EXIT, STATE_PREV, STATE_ZERO};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+/// Problem found in debot state machine by `validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// Debot abi is not a valid json abi.
+    InvalidAbi(String),
+    /// Debot has no context with id `STATE_ZERO`.
+    NoStartContext,
+    /// Action switches to context which does not exist.
+    UnknownTarget { context: u8, action: String, to: u8 },
+    /// Context cannot be reached from `STATE_ZERO`.
+    UnreachableContext(u8),
+    /// `RunAction` or `SendMsg` action refers to function missing in debot abi.
+    UnknownFunction { context: u8, action: String },
+    /// `RunMethod` action refers to get-method missing in target abi.
+    UnknownGetmethod { context: u8, action: String, func: String },
+    /// `RunMethod` action is used but debot does not define target abi.
+    TargetAbiUndefined { context: u8, action: String },
+    /// `CallEngine` action refers to unknown engine routine.
+    UnknownRoutine { context: u8, action: String },
+    /// Action attributes string is malformed.
+    InvalidAttrs { context: u8, action: String, attrs: String },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::InvalidAbi(msg) => write!(f, "invalid abi: {}", msg),
+            ValidationIssue::NoStartContext => write!(f, "context #{} not found", STATE_ZERO),
+            ValidationIssue::UnknownTarget { context, action, to } => write!(
+                f, "context #{}, action {}: target context #{} not found", context, action, to,
+            ),
+            ValidationIssue::UnreachableContext(id) => write!(f, "context #{} is unreachable", id),
+            ValidationIssue::UnknownFunction { context, action } => write!(
+                f, "context #{}, action {}: function not found in debot abi", context, action,
+            ),
+            ValidationIssue::UnknownGetmethod { context, action, func } => write!(
+                f, "context #{}, action {}: get-method {} not found in target abi", context, action, func,
+            ),
+            ValidationIssue::TargetAbiUndefined { context, action } => write!(
+                f, "context #{}, action {}: target abi is undefined", context, action,
+            ),
+            ValidationIssue::UnknownRoutine { context, action } => write!(
+                f, "context #{}, action {}: unknown engine routine", context, action,
+            ),
+            ValidationIssue::InvalidAttrs { context, action, attrs } => write!(
+                f, "context #{}, action {}: invalid attributes \"{}\"", context, action, attrs,
+            ),
+        }
+    }
+}
+
+/// Checks debot state machine for broken links, unreachable contexts
+/// and actions which refer to missing functions or routines.
+pub fn validate(
+    contexts: &[DContext],
+    abi: &str,
+    target_abi: Option<&str>,
+    routines: &[&str],
+) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+    let functions = match abi_functions(abi) {
+        Ok(functions) => functions,
+        Err(e) => {
+            issues.push(ValidationIssue::InvalidAbi(e));
+            HashSet::new()
+        },
+    };
+    // None if target abi is undefined
+    let getmethods = match target_abi.map(abi_functions).transpose() {
+        Ok(getmethods) => getmethods,
+        Err(e) => {
+            issues.push(ValidationIssue::InvalidAbi(e));
+            Some(HashSet::new())
+        },
+    };
+    let ids: HashSet<u8> = contexts.iter().map(|ctx| ctx.id).collect();
+
+    for ctx in contexts {
+        for act in &ctx.actions {
+            if !is_special_state(act.to) && !ids.contains(&act.to) {
+                issues.push(ValidationIssue::UnknownTarget {
+                    context: ctx.id,
+                    action: act.name.clone(),
+                    to: act.to,
+                });
+            }
+            if act.parse_attrs().is_err() {
+                issues.push(ValidationIssue::InvalidAttrs {
+                    context: ctx.id,
+                    action: act.name.clone(),
+                    attrs: act.attrs().to_owned(),
+                });
+            }
+            match act.action_type {
+                AcType::RunAction | AcType::SendMsg if !functions.contains(&act.name) => {
+                    issues.push(ValidationIssue::UnknownFunction {
+                        context: ctx.id,
+                        action: act.name.clone(),
+                    });
+                },
+                AcType::RunMethod => {
+                    let func = act.func_attr().unwrap_or_default();
+                    match &getmethods {
+                        None => issues.push(ValidationIssue::TargetAbiUndefined {
+                            context: ctx.id,
+                            action: act.name.clone(),
+                        }),
+                        Some(getmethods) if !getmethods.contains(&func) => {
+                            issues.push(ValidationIssue::UnknownGetmethod {
+                                context: ctx.id,
+                                action: act.name.clone(),
+                                func,
+                            });
+                        },
+                        Some(_) => {},
+                    }
+                },
+                AcType::CallEngine if !routines.contains(&act.name.as_str()) => {
+                    issues.push(ValidationIssue::UnknownRoutine {
+                        context: ctx.id,
+                        action: act.name.clone(),
+                    });
+                },
+                _ => {},
+            }
+        }
+    }
+
+    if !ids.contains(&STATE_ZERO) {
+        issues.push(ValidationIssue::NoStartContext);
+    } else {
+        let reachable = reachable_contexts(contexts);
+        for ctx in contexts.iter().filter(|ctx| !reachable.contains(&ctx.id)) {
+            issues.push(ValidationIssue::UnreachableContext(ctx.id));
+        }
+    }
+    issues
+}
+
+fn is_special_state(id: u8) -> bool {
+    id == STATE_EXIT || id == STATE_PREV || id == STATE_CURRENT
+}
+
+fn abi_functions(abi: &str) -> Result<HashSet<String>, String> {
+    let abi_json: serde_json::Value = serde_json::from_str(abi)
+        .map_err(|e| format!("abi is not a valid json: {}", e))?;
+    let functions = abi_json["functions"].as_array()
+        .ok_or_else(|| "abi has no functions".to_owned())?;
+    Ok(functions.iter()
+        .filter_map(|f| f["name"].as_str())
+        .map(|name| name.to_owned())
+        .collect())
+}
+
+fn reachable_contexts(contexts: &[DContext]) -> HashSet<u8> {
+    let mut reachable = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(STATE_ZERO);
+    while let Some(id) = queue.pop_front() {
+        if !reachable.insert(id) {
+            continue;
+        }
+        let targets = contexts.iter()
+            .filter(|ctx| ctx.id == id)
+            .flat_map(|ctx| ctx.actions.iter())
+            .map(|act: &DAction| act.to)
+            .filter(|to| !is_special_state(*to));
+        queue.extend(targets);
+    }
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABI: &str = r#"{"functions": [{"name": "transfer"}]}"#;
+    const TARGET_ABI: &str = r#"{"functions": [{"name": "getBalance"}]}"#;
+    const ROUTINES: &[&str] = &["convertTokens", "returnResult"];
+
+    fn action(name: &str, action_type: u8, to: u8, attrs: &str) -> DAction {
+        let mut act = DAction::new(String::new(), name.to_owned(), action_type, to);
+        act.set_attrs(attrs.to_owned());
+        act
+    }
+
+    #[test]
+    fn test_valid_debot() {
+        let contexts = vec![
+            DContext::new("Main".to_owned(), vec![
+                action("transfer", 3, 1, "sign=by_user"),
+                action("Quit", 0, STATE_EXIT, ""),
+                action("returnResult", 10, STATE_EXIT, ""),
+            ], STATE_ZERO),
+            DContext::new("Balance".to_owned(), vec![
+                action("setBalance", 2, STATE_PREV, "instant,func=getBalance"),
+                action("convertTokens", 10, STATE_CURRENT, "instant,func=setTokens,args=getArgs"),
+            ], 1),
+        ];
+        assert_eq!(validate(&contexts, ABI, Some(TARGET_ABI), ROUTINES), vec![]);
+    }
+
+    #[test]
+    fn test_broken_debot() {
+        let contexts = vec![
+            DContext::new("Main".to_owned(), vec![
+                action("send", 3, 2, ""),
+                action("setBalance", 2, STATE_CURRENT, "func=getTotal"),
+                action("sendAll", 10, STATE_CURRENT, "func=setAll"),
+                action("Quit", 0, STATE_EXIT, "instant,sign"),
+            ], STATE_ZERO),
+            DContext::new("Lost".to_owned(), vec![], 1),
+        ];
+        assert_eq!(validate(&contexts, ABI, Some(TARGET_ABI), ROUTINES), vec![
+            ValidationIssue::UnknownTarget { context: 0, action: "send".to_owned(), to: 2 },
+            ValidationIssue::UnknownFunction { context: 0, action: "send".to_owned() },
+            ValidationIssue::UnknownGetmethod {
+                context: 0,
+                action: "setBalance".to_owned(),
+                func: "getTotal".to_owned(),
+            },
+            ValidationIssue::UnknownRoutine { context: 0, action: "sendAll".to_owned() },
+            ValidationIssue::InvalidAttrs {
+                context: 0,
+                action: "Quit".to_owned(),
+                attrs: "instant,sign".to_owned(),
+            },
+            ValidationIssue::UnreachableContext(1),
+        ]);
+    }
+
+    #[test]
+    fn test_target_abi_undefined() {
+        let contexts = vec![
+            DContext::new("Main".to_owned(), vec![
+                action("setBalance", 2, STATE_CURRENT, "func=getBalance"),
+            ], STATE_ZERO),
+        ];
+        assert_eq!(validate(&contexts, ABI, None, ROUTINES), vec![
+            ValidationIssue::TargetAbiUndefined { context: 0, action: "setBalance".to_owned() },
+        ]);
+    }
+
+    #[test]
+    fn test_no_start_context() {
+        let contexts = vec![DContext::new("Lost".to_owned(), vec![], 1)];
+        let issues = validate(&contexts, "not json", None, ROUTINES);
+        assert_eq!(issues.len(), 2);
+        assert!(matches!(issues[0], ValidationIssue::InvalidAbi(_)));
+        assert_eq!(issues[1], ValidationIssue::NoStartContext);
+    }
+}
@@ -1,28 +1,109 @@
-use debot_engine::{BrowserCallbacks, DAction, DEngine};
-use std::cell::RefCell;
-use std::rc::Rc;
-use ton_client_rs::{Ed25519KeyPair, TonAddress};
+#[macro_use] extern crate serde_json;
 
-struct TestBrowser {}
+use async_trait::async_trait;
+use debot_engine::{AcType, AsyncBrowserCallbacks, BrowserCallbacks, DAction, DEngine, DebotBackend,
+    DEngineError, DEngineSession, InputRequest, InputResult, MessageCall, MessageFees, RecordingBackend,
+    ReplayBackend, RoutineRegistry, SignPurpose, Signer, ValidationIssue, STATE_CURRENT, STATE_EXIT, STATE_PREV,
+    STATE_ZERO};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use ton_client_rs::{DecodedMessageBody, EncodedMessage, JsonValue, LocalRunFees, ResultOfLocalRun,
+    TonAddress, TonError, TonErrorKind, TonResult, UnsignedMessage};
+
+struct TestBrowser {
+    switches: Vec<u8>,
+    /// Values entered by user, the first is used first.
+    /// User cancels input when values run out.
+    inputs: Vec<String>,
+    requests: Vec<InputRequest>,
+    /// What user signed.
+    signed: Vec<SignPurpose>,
+    /// Messages which user was asked to approve.
+    confirmed: Vec<MessageCall>,
+    /// User approves messages.
+    approve: bool,
+    logs: Vec<String>,
+}
 
 impl TestBrowser {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            switches: vec![],
+            inputs: vec![],
+            requests: vec![],
+            signed: vec![],
+            confirmed: vec![],
+            approve: true,
+            logs: vec![],
+        }
+    }
+}
+
+struct TestSigner {
+    browser: Arc<Mutex<TestBrowser>>,
+}
+
+impl Signer for TestSigner {
+    fn public_key(&self) -> Result<Vec<u8>, String> {
+        Ok(vec![0; 32])
+    }
+    fn sign(&self, _data: &[u8], purpose: &SignPurpose) -> Result<Vec<u8>, String> {
+        self.browser.lock().unwrap().signed.push(purpose.clone());
+        Ok(vec![0; 64])
     }
 }
 
 struct TestCallbacks {
-    #[allow(dead_code)]
-    browser: Rc<RefCell<TestBrowser>>,
+    browser: Arc<Mutex<TestBrowser>>,
 }
 
 impl TestCallbacks {
-    pub fn new(browser: Rc<RefCell<TestBrowser>>) -> Self {
+    pub fn new(browser: Arc<Mutex<TestBrowser>>) -> Self {
         Self { browser }
     }
 }
 
 impl BrowserCallbacks for TestCallbacks {
+    fn log(&self, msg: String) {
+        println!("log: {}", msg);
+        self.browser.lock().unwrap().logs.push(msg);
+    }
+    fn switch(&self, ctx_id: u8) {
+        println!("switch to {}", ctx_id);
+        self.browser.lock().unwrap().switches.push(ctx_id);
+    }
+    fn show_action(&self, act: DAction) {
+        println!("show_action {}", act.name);
+    }
+    fn input(&self, request: &InputRequest) -> InputResult {
+        println!("input: {} ({})", request.name, request.abi_type);
+        let mut browser = self.browser.lock().unwrap();
+        browser.requests.push(request.clone());
+        if browser.inputs.is_empty() {
+            InputResult::Cancelled
+        } else {
+            InputResult::Value(browser.inputs.remove(0))
+        }
+    }
+    fn signer(&self) -> Option<Box<dyn Signer>> {
+        println!("signer");
+        Some(Box::new(TestSigner { browser: Arc::clone(&self.browser) }))
+    }
+    fn confirm_message(&self, msg: &MessageCall) -> bool {
+        println!("confirm: {} at {}", msg.func, msg.dest);
+        let mut browser = self.browser.lock().unwrap();
+        browser.confirmed.push(msg.clone());
+        browser.approve
+    }
+}
+
+struct AsyncTestCallbacks {}
+
+#[async_trait]
+impl AsyncBrowserCallbacks for AsyncTestCallbacks {
     fn log(&self, msg: String) {
         println!("log: {}", msg);
     }
@@ -32,23 +113,25 @@ impl BrowserCallbacks for TestCallbacks {
     fn show_action(&self, act: DAction) {
         println!("show_action {}", act.name);
     }
-    fn input(&self, prefix: &str, value: &mut String) {
-        println!("input: {}", prefix);
-        *value = String::new();
+    async fn input(&self, request: &InputRequest) -> InputResult {
+        println!("input: {} ({})", request.name, request.abi_type);
+        InputResult::Cancelled
     }
-    fn load_key(&self, _keys: &mut Ed25519KeyPair) {
-        println!("load_key");
+    async fn signer(&self) -> Option<Box<dyn Signer>> {
+        println!("signer");
+        None
     }
-    fn invoke_debot(&self, _debot: TonAddress, _action: DAction) -> Result<(), String> {
-        println!("invoke_debot");
-        Ok(())
+    async fn confirm_message(&self, msg: &MessageCall) -> bool {
+        println!("confirm: {} at {}", msg.func, msg.dest);
+        false
     }
 }
 
 #[test]
+#[ignore] // requires access to net.ton.dev
 fn test_create_dengine() {
-    let browser = Rc::new(RefCell::new(TestBrowser::new()));
-    let callbacks = Box::new(TestCallbacks::new(Rc::clone(&browser)));
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let callbacks = Box::new(TestCallbacks::new(Arc::clone(&browser)));
     let mut engine = DEngine::new(
         TonAddress::from_str("0:ca7dd7c6db6cad5264285540609e503c08aa97b2c4ae30fd0652ee14dd9d3a4b")
             .unwrap(),
@@ -59,3 +142,1113 @@ fn test_create_dengine() {
 
     engine.start().unwrap();
 }
+
+
+const DEBOT_ADDR: &str = "0:ca7dd7c6db6cad5264285540609e503c08aa97b2c4ae30fd0652ee14dd9d3a4b";
+const CHILD_DEBOT_ADDR: &str = "0:1111111111111111111111111111111111111111111111111111111111111111";
+const EMPTY_CELL: &str = "te6ccgEBAQEAAgAAAA==";
+
+fn action_json(name: &str, action_type: u8, to: u8, attrs: &str) -> serde_json::Value {
+    json!({
+        "desc": "",
+        "name": hex::encode(name),
+        "actionType": format!("0x{:x}", action_type),
+        "to": format!("0x{:x}", to),
+        "attrs": hex::encode(attrs),
+        "misc": EMPTY_CELL,
+    })
+}
+
+fn context_json(id: u8, desc: &str, actions: Vec<serde_json::Value>) -> serde_json::Value {
+    json!({
+        "id": format!("0x{:x}", id),
+        "desc": hex::encode(desc),
+        "actions": actions,
+    })
+}
+
+/// Engine for debot at `DEBOT_ADDR` with optional debot `abi`
+/// which is served by `backend` and talks to `browser`.
+fn engine(
+    abi: Option<&serde_json::Value>,
+    backend: impl DebotBackend + 'static,
+    browser: &Arc<Mutex<TestBrowser>>,
+) -> DEngine {
+    DEngine::new_with_backend(
+        TonAddress::from_str(DEBOT_ADDR).unwrap(),
+        abi.map(|abi| abi.to_string()),
+        Box::new(backend),
+        Box::new(TestCallbacks::new(Arc::clone(browser))),
+    )
+}
+
+/// Debot backend which serves debot contexts from memory.
+struct MockBackend {
+    contexts: serde_json::Value,
+    debots: HashMap<String, serde_json::Value>,
+    outputs: HashMap<String, serde_json::Value>,
+    /// Function and arguments of message body returned by debot.
+    message_call: Option<(String, serde_json::Value)>,
+    /// Names and arguments of debot functions run by engine
+    /// and names of functions called by sent messages.
+    calls: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+    /// Accounts returned by `query_accounts`.
+    accounts: Vec<serde_json::Value>,
+}
+
+impl MockBackend {
+    /// Tiny debot with one context.
+    fn new() -> Self {
+        MockBackend::with_contexts(json!([
+            context_json(0, "Main menu", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+        ]))
+    }
+
+    /// Debot with one context which sends `transfer` message signed by user.
+    fn send_transfer() -> Self {
+        MockBackend::with_contexts(json!([
+            context_json(0, "Main menu", vec![action_json("sendTransfer", 3, STATE_EXIT, "sign=by_user")]),
+        ]))
+        .with_output("sendTransfer", json!({ "dest": DEBOT_ADDR, "body": base64::encode("body") }))
+        .with_message_call("transfer", json!({ "value": "0x1" }))
+    }
+
+    fn with_contexts(contexts: serde_json::Value) -> Self {
+        MockBackend {
+            contexts,
+            debots: HashMap::new(),
+            outputs: HashMap::new(),
+            message_call: None,
+            calls: Arc::new(Mutex::new(vec![])),
+            accounts: vec![],
+        }
+    }
+
+    /// Adds another debot with address `addr`.
+    fn with_debot(mut self, addr: &str, contexts: serde_json::Value) -> Self {
+        self.debots.insert(TonAddress::from_str(addr).unwrap().to_string(), contexts);
+        self
+    }
+
+    /// Sets function and arguments decoded from any message body.
+    fn with_message_call(mut self, func: &str, args: serde_json::Value) -> Self {
+        self.message_call = Some((func.to_owned(), args));
+        self
+    }
+
+    fn message(id: &str) -> EncodedMessage {
+        EncodedMessage { address: None, message_id: id.to_owned(), expire: None, message_body: vec![] }
+    }
+
+    fn with_account(mut self, account: serde_json::Value) -> Self {
+        self.accounts.push(account);
+        self
+    }
+
+    /// Sets output of debot function `func`.
+    fn with_output(mut self, func: &str, output: serde_json::Value) -> Self {
+        self.outputs.insert(func.to_owned(), output);
+        self
+    }
+
+    fn not_supported<T>(op: &str) -> TonResult<T> {
+        Err(TonError::from_kind(TonErrorKind::Msg(format!("{} is not supported by mock", op))))
+    }
+}
+
+#[async_trait]
+impl DebotBackend for MockBackend {
+    async fn run_local(
+        &self,
+        addr: &TonAddress,
+        _account: Option<JsonValue>,
+        _abi: JsonValue,
+        func: &str,
+        args: JsonValue,
+        _emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        let args = serde_json::from_str(&args.to_string()).unwrap_or_default();
+        self.calls.lock().unwrap().push((func.to_owned(), args));
+        let output = match func {
+            "getVersion" => json!({
+                "name": hex::encode("MockDebot"),
+                "semver": "0x000100",
+            }),
+            "getDebotOptions" => json!({
+                "options": "0x0",
+                "debotAbi": "",
+                "targetAbi": "",
+                "targetAddr": DEBOT_ADDR,
+            }),
+            "fetch" => json!({
+                "contexts": self.debots.get(&addr.to_string()).unwrap_or(&self.contexts),
+            }),
+            _ => match self.outputs.get(func) {
+                Some(output) => output.clone(),
+                None => return MockBackend::not_supported(func),
+            },
+        };
+        Ok(ResultOfLocalRun { output, fees: None, account: Some(json!({})) })
+    }
+
+    async fn run_local_msg(
+        &self,
+        _addr: &TonAddress,
+        _account: Option<JsonValue>,
+        msg: EncodedMessage,
+        _abi: JsonValue,
+        func: &str,
+        _emulate_real_txn: bool,
+    ) -> TonResult<ResultOfLocalRun> {
+        self.calls.lock().unwrap().push((func.to_owned(), json!({ "emulated": msg.message_id })));
+        let fees = LocalRunFees {
+            in_msg_fwd_fee: 1,
+            storage_fee: 2,
+            gas_fee: 3,
+            out_msgs_fwd_fee: 4,
+            total_account_fees: 10,
+            total_output: 0,
+        };
+        Ok(ResultOfLocalRun { output: json!({ "result": "0x1" }), fees: Some(fees), account: None })
+    }
+
+    async fn create_run_message(
+        &self,
+        _addr: &TonAddress,
+        _abi: JsonValue,
+        _func: &str,
+        _args: JsonValue,
+    ) -> TonResult<EncodedMessage> {
+        Ok(MockBackend::message("unsigned"))
+    }
+
+    async fn create_unsigned_run_message(
+        &self,
+        _addr: &TonAddress,
+        _abi: JsonValue,
+        _func: &str,
+        _args: JsonValue,
+    ) -> TonResult<UnsignedMessage> {
+        Ok(UnsignedMessage { message: vec![1], data_to_sign: vec![2; 32], expire: None })
+    }
+
+    async fn add_sign_to_message(
+        &self,
+        _signature: &[u8],
+        _public_key: &[u8],
+        _message: &[u8],
+    ) -> TonResult<EncodedMessage> {
+        Ok(MockBackend::message("signed"))
+    }
+
+    async fn process_message(
+        &self,
+        msg: EncodedMessage,
+        _abi: JsonValue,
+        func: &str,
+    ) -> TonResult<serde_json::Value> {
+        self.calls.lock().unwrap().push((func.to_owned(), json!({ "messageId": msg.message_id })));
+        Ok(json!({}))
+    }
+
+    async fn decode_input_message_body(
+        &self,
+        _abi: JsonValue,
+        _body: &[u8],
+        _internal: bool,
+    ) -> TonResult<DecodedMessageBody> {
+        match &self.message_call {
+            Some((function, output)) => Ok(DecodedMessageBody {
+                function: function.clone(),
+                output: output.clone(),
+            }),
+            None => MockBackend::not_supported("decode_input_message_body"),
+        }
+    }
+
+    async fn query_accounts(
+        &self,
+        filter: JsonValue,
+        _result: &str,
+    ) -> TonResult<Vec<serde_json::Value>> {
+        let filter: serde_json::Value = serde_json::from_str(&filter.to_string()).unwrap_or_default();
+        Ok(self.accounts.iter().filter(|acc| acc["id"] == filter["id"]["eq"]).cloned().collect())
+    }
+}
+
+#[test]
+fn test_start_with_mock_backend() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let mut engine = engine(None, MockBackend::new(), &browser);
+
+    engine.start().unwrap();
+}
+
+/// Starts debot and runs the first action of current context `steps` times.
+fn run_first_actions(engine: &mut DEngine, steps: usize) {
+    engine.start().unwrap();
+    for _ in 0..steps {
+        let action = engine.current_context().unwrap().actions[0].clone();
+        engine.execute_action(&action).unwrap();
+    }
+}
+
+#[test]
+fn test_record_and_replay_session() {
+    let fixture = std::env::temp_dir().join("debot_engine_replay_test.json");
+    let abi = json!({
+        "functions": [
+            { "name": "increment", "inputs": [], "outputs": [] },
+            { "name": "sendTransfer", "inputs": [], "outputs": [] },
+        ],
+    });
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("increment", 1, 1, "")]),
+        context_json(1, "Transfer", vec![action_json("sendTransfer", 3, STATE_EXIT, "sign=by_user")]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_output("increment", json!(null))
+        .with_output("sendTransfer", json!({ "dest": DEBOT_ADDR, "body": base64::encode("body") }))
+        .with_message_call("transfer", json!({ "value": "0x1" }));
+
+    let recorded = Arc::new(Mutex::new(TestBrowser::new()));
+    let recorder = RecordingBackend::new(Box::new(backend), &fixture);
+    run_first_actions(&mut engine(Some(&abi), recorder, &recorded), 2);
+
+    let replayed = Arc::new(Mutex::new(TestBrowser::new()));
+    let replay = ReplayBackend::load(&fixture).unwrap();
+    std::fs::remove_file(&fixture).unwrap();
+    run_first_actions(&mut engine(Some(&abi), replay, &replayed), 2);
+
+    let recorded = recorded.lock().unwrap();
+    let replayed = replayed.lock().unwrap();
+    assert_eq!(recorded.switches, vec![STATE_ZERO, 1, STATE_EXIT]);
+    assert!(recorded.logs.contains(&"Transaction succeeded.".to_owned()));
+    assert_eq!(replayed.switches, recorded.switches);
+    assert_eq!(replayed.logs, recorded.logs);
+}
+
+#[test]
+fn test_replay_fees_and_args() {
+    let fixture = std::env::temp_dir().join("debot_engine_replay_args_test.json");
+    let records = json!([
+        {
+            "op": "run_local",
+            "func": "getBalance",
+            "args": { "addr": DEBOT_ADDR },
+            "result": {
+                "output": { "balance": "0x1" },
+                "fees": {
+                    "inMsgFwdFee": 1,
+                    "storageFee": 2,
+                    "gasFee": 3,
+                    "outMsgsFwdFee": 4,
+                    "totalAccountFees": 10,
+                    "totalOutput": 0,
+                },
+            },
+        },
+        { "op": "run_local", "func": "getBalance", "args": { "addr": DEBOT_ADDR }, "result": {} },
+    ]);
+    std::fs::write(&fixture, records.to_string()).unwrap();
+    let replay = ReplayBackend::load(&fixture).unwrap();
+    std::fs::remove_file(&fixture).unwrap();
+    let addr = TonAddress::from_str(DEBOT_ADDR).unwrap();
+
+    let res = futures::executor::block_on(replay.run_local(
+        &addr, None, json!({}).into(), "getBalance", json!({ "addr": DEBOT_ADDR }).into(), false,
+    )).unwrap();
+    assert_eq!(res.output, json!({ "balance": "0x1" }));
+    assert_eq!(res.fees.map(|fees| fees.total_account_fees), Some(10));
+
+    let res = futures::executor::block_on(replay.run_local(
+        &addr, None, json!({}).into(), "getBalance", json!({ "addr": CHILD_DEBOT_ADDR }).into(), false,
+    ));
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_malformed_contexts() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([{
+        "id": "0x0",
+        "desc": "not a hex string",
+        "actions": [],
+    }]);
+    let mut engine = engine(None, MockBackend::with_contexts(contexts), &browser);
+
+    assert!(engine.start().is_err());
+}
+
+#[test]
+fn test_start_with_invalid_attrs() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![
+            action_json("Next", 0, 1, "instant,color=red"),
+            action_json("Quit", 0, STATE_EXIT, ""),
+        ]),
+        context_json(1, "Step 1", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    let mut engine = engine(None, MockBackend::with_contexts(contexts), &browser);
+
+    engine.start().unwrap();
+    assert_eq!(engine.current_state(), 1);
+    let warning = r#"Warning: context #0, action Next: invalid attributes "instant,color=red": unknown attribute "color""#;
+    assert!(browser.lock().unwrap().logs.contains(&warning.to_owned()));
+    assert_eq!(engine.validate(), vec![ValidationIssue::InvalidAttrs {
+        context: STATE_ZERO,
+        action: "Next".to_owned(),
+        attrs: "instant,color=red".to_owned(),
+    }]);
+}
+
+#[test]
+fn test_start_async() {
+    let mut engine = DEngine::new_with_async_browser(
+        TonAddress::from_str(DEBOT_ADDR).unwrap(),
+        None,
+        Box::new(MockBackend::new()),
+        Box::new(AsyncTestCallbacks {}),
+    );
+
+    futures::executor::block_on(engine.start_async()).unwrap();
+}
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[test]
+fn test_async_futures_are_send() {
+    let mut engine = DEngine::new_with_async_browser(
+        TonAddress::from_str(DEBOT_ADDR).unwrap(),
+        None,
+        Box::new(MockBackend::new()),
+        Box::new(AsyncTestCallbacks {}),
+    );
+    let action = DAction::new(String::new(), "increment".to_owned(), 1, STATE_CURRENT);
+    assert_send(&engine.start_async());
+    assert_send(&engine.execute_action_async(&action));
+}
+
+#[test]
+fn test_move_engine_between_threads() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let mut engine = engine(None, MockBackend::new(), &browser);
+    engine.start().unwrap();
+
+    let quit = DAction::new(String::new(), "Quit".to_owned(), 0, STATE_EXIT);
+    std::thread::spawn(move || engine.execute_action(&quit))
+        .join()
+        .unwrap()
+        .unwrap();
+}
+
+/// Future which is pending on the first poll.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// Async browser which makes engine wait for user input.
+struct PendingInputCallbacks {}
+
+#[async_trait]
+impl AsyncBrowserCallbacks for PendingInputCallbacks {
+    fn log(&self, msg: String) {
+        println!("log: {}", msg);
+    }
+    fn switch(&self, ctx_id: u8) {
+        println!("switch to {}", ctx_id);
+    }
+    fn show_action(&self, act: DAction) {
+        println!("show_action {}", act.name);
+    }
+    async fn input(&self, _request: &InputRequest) -> InputResult {
+        YieldOnce(false).await;
+        InputResult::Value("42".to_owned())
+    }
+    async fn signer(&self) -> Option<Box<dyn Signer>> {
+        None
+    }
+    async fn confirm_message(&self, _msg: &MessageCall) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_move_action_future_between_threads() {
+    let abi = json!({
+        "functions": [{
+            "name": "setValue",
+            "inputs": [{"name": "value", "type": "uint8"}],
+            "outputs": [],
+        }],
+    });
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("setValue", 1, STATE_EXIT, "")]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts).with_output("setValue", json!(null));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = DEngine::new_with_async_browser(
+        TonAddress::from_str(DEBOT_ADDR).unwrap(),
+        Some(abi.to_string()),
+        Box::new(backend),
+        Box::new(PendingInputCallbacks {}),
+    );
+    futures::executor::block_on(engine.start_async()).unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+
+    // action is suspended while waiting for input, then finished on another thread
+    let mut action_future = Box::pin(engine.execute_action_async(&action));
+    let waker = futures::task::noop_waker();
+    assert!(action_future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+    std::thread::scope(|scope| {
+        scope.spawn(move || futures::executor::block_on(action_future)).join().unwrap()
+    }).unwrap();
+
+    assert_eq!(engine.current_state(), STATE_EXIT);
+    let calls = calls.lock().unwrap();
+    assert!(calls.contains(&("setValue".to_owned(), json!({"value": "0x2a"}))));
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let mut engine = engine(None, MockBackend::new(), &browser);
+    engine.start().unwrap();
+    let saved = engine.snapshot().unwrap().to_json().unwrap();
+    drop(engine);
+
+    let session = DEngineSession::from_json(&saved).unwrap();
+    let mut engine = DEngine::restore(
+        session,
+        Box::new(MockBackend::new()),
+        Box::new(TestCallbacks::new(Arc::clone(&browser))),
+    ).unwrap();
+    assert_eq!(saved, engine.snapshot().unwrap().to_json().unwrap());
+
+    engine.resume().unwrap();
+    let quit = DAction::new(String::new(), "Quit".to_owned(), 0, STATE_EXIT);
+    engine.execute_action(&quit).unwrap();
+}
+
+#[test]
+fn test_navigation_history() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("Next", 0, 1, "")]),
+        context_json(1, "Step 1", vec![action_json("Next", 0, 2, "")]),
+        context_json(2, "Step 2", vec![action_json("Back", 0, STATE_PREV, "")]),
+    ]);
+    let mut engine = engine(None, MockBackend::with_contexts(contexts), &browser);
+    engine.start().unwrap();
+    assert_eq!(engine.history(), &[] as &[u8]);
+
+    engine.execute_action(&DAction::new(String::new(), "Next".to_owned(), 0, 1)).unwrap();
+    engine.execute_action(&DAction::new(String::new(), "Next".to_owned(), 0, 2)).unwrap();
+    assert_eq!(engine.history(), &[STATE_ZERO, 1]);
+
+    engine.execute_action(&DAction::new(String::new(), "Back".to_owned(), 0, STATE_PREV)).unwrap();
+    assert_eq!(engine.history(), &[STATE_ZERO]);
+    engine.back().unwrap();
+    assert_eq!(engine.history(), &[] as &[u8]);
+    engine.back().unwrap();
+
+    let switches = browser.lock().unwrap().switches.clone();
+    assert_eq!(switches, vec![STATE_ZERO, 1, 2, 1, STATE_ZERO, STATE_EXIT]);
+}
+
+#[test]
+fn test_engine_getters() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("Next", 0, 1, "")]),
+        context_json(1, "Step 1", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    let mut engine = engine(None, MockBackend::with_contexts(contexts), &browser);
+    engine.start().unwrap();
+    assert_eq!(engine.contexts().len(), 2);
+    assert_eq!(engine.current_state(), STATE_ZERO);
+    assert_eq!(engine.current_context().unwrap().desc, "Main menu");
+    assert!(engine.target_addr().is_none());
+    assert!(engine.target_abi().is_none());
+
+    engine.execute_action(&DAction::new(String::new(), "Next".to_owned(), 0, 1)).unwrap();
+    assert_eq!(engine.current_state(), 1);
+    assert_eq!(engine.current_context().unwrap().desc, "Step 1");
+
+    engine.execute_action(&DAction::new(String::new(), "Quit".to_owned(), 0, STATE_EXIT)).unwrap();
+    assert_eq!(engine.current_state(), STATE_EXIT);
+    assert!(engine.current_context().is_none());
+}
+
+#[test]
+fn test_back_skips_instant_redirect() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("Next", 0, 1, "")]),
+        context_json(1, "Redirect", vec![action_json("Redirect", 0, 2, "instant")]),
+        context_json(2, "Step 2", vec![action_json("Back", 0, STATE_PREV, "")]),
+    ]);
+    let mut engine = engine(None, MockBackend::with_contexts(contexts), &browser);
+    let next = DAction::new(String::new(), "Next".to_owned(), 0, 1);
+    engine.start().unwrap();
+    engine.execute_action(&next).unwrap();
+    assert_eq!(engine.current_state(), 2);
+    assert_eq!(engine.history(), &[STATE_ZERO]);
+
+    engine.back().unwrap();
+    assert_eq!(engine.current_state(), STATE_ZERO);
+
+    engine.execute_action(&next).unwrap();
+    engine.start().unwrap();
+    assert_eq!(engine.history(), &[] as &[u8]);
+}
+
+#[test]
+fn test_instant_switch_cycle() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("Go", 0, 1, "instant")]),
+        context_json(1, "Ping", vec![action_json("Ping", 0, 2, "instant")]),
+        context_json(2, "Pong", vec![action_json("Pong", 0, 1, "instant")]),
+    ]);
+    let mut engine = engine(None, MockBackend::with_contexts(contexts), &browser);
+
+    assert_eq!(engine.start(), Err(DEngineError::InstantSwitchLoop(vec![1, 2, 1])));
+}
+
+#[test]
+fn test_max_instant_switches() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("Go", 0, 1, "instant")]),
+        context_json(1, "Step 1", vec![action_json("Go", 0, 2, "instant")]),
+        context_json(2, "Step 2", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    let mut engine = engine(None, MockBackend::with_contexts(contexts), &browser);
+    engine.set_max_instant_switches(1);
+
+    assert_eq!(engine.start(), Err(DEngineError::InstantSwitchLoop(vec![STATE_ZERO, 1, 2])));
+}
+
+#[test]
+fn test_invoke_debot() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("invokeChild", 4, 1, "")]),
+        context_json(1, "Done", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    let child_contexts = json!([
+        context_json(0, "Child menu", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+        context_json(1, "Child page", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_debot(CHILD_DEBOT_ADDR, child_contexts)
+        .with_output("invokeChild", json!({
+            "debot": CHILD_DEBOT_ADDR,
+            "action": action_json("Open", 6, 1, ""),
+        }));
+    let mut engine = engine(None, backend, &browser);
+    engine.start().unwrap();
+
+    let mut invoke = DAction::new(String::new(), "invokeChild".to_owned(), 0, 1);
+    invoke.action_type = AcType::Invoke;
+    engine.execute_action(&invoke).unwrap();
+    assert_eq!(engine.invoke_depth(), 1);
+    assert_eq!(engine.current_context().unwrap().desc, "Child page");
+
+    engine.execute_action(&DAction::new(String::new(), "Quit".to_owned(), 0, STATE_EXIT)).unwrap();
+    assert_eq!(engine.invoke_depth(), 0);
+    assert_eq!(engine.current_context().unwrap().desc, "Done");
+    assert_eq!(engine.history(), &[STATE_ZERO]);
+
+    let switches = browser.lock().unwrap().switches.clone();
+    assert_eq!(switches, vec![STATE_ZERO, STATE_ZERO, 1, 1]);
+}
+
+#[test]
+fn test_invoke_result_handler_fails() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("invokeChild", 4, 2, "func=onResult")]),
+        context_json(2, "Done", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    let child_contexts = json!([
+        context_json(0, "Child menu", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+        context_json(1, "Child page", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    // caller has no `onResult` function
+    let backend = MockBackend::with_contexts(contexts)
+        .with_debot(CHILD_DEBOT_ADDR, child_contexts)
+        .with_output("invokeChild", json!({
+            "debot": CHILD_DEBOT_ADDR,
+            "action": action_json("Open", 6, 1, ""),
+        }));
+    let mut engine = engine(None, backend, &browser);
+    engine.start().unwrap();
+
+    let mut invoke = DAction::new(String::new(), "invokeChild".to_owned(), 0, 2);
+    invoke.action_type = AcType::Invoke;
+    invoke.set_attrs("func=onResult".to_owned());
+    engine.execute_action(&invoke).unwrap();
+    assert_eq!(engine.current_context().unwrap().desc, "Child page");
+    assert!(matches!(engine.snapshot(), Err(DEngineError::Session(_))));
+
+    engine.execute_action(&DAction::new(String::new(), "Quit".to_owned(), 0, STATE_EXIT)).unwrap();
+    assert_eq!(engine.invoke_depth(), 0);
+    assert_eq!(engine.current_context().unwrap().desc, "Done");
+    assert_eq!(engine.history(), &[STATE_ZERO]);
+    assert!(engine.snapshot().is_ok());
+}
+
+#[test]
+fn test_max_invoke_depth() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("invokeSelf", 4, 0, "instant")]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_output("invokeSelf", json!({
+            "debot": DEBOT_ADDR,
+            "action": action_json("Stay", 6, 0, ""),
+        }));
+    let mut engine = engine(None, backend, &browser);
+    engine.set_max_invoke_depth(2);
+
+    assert_eq!(engine.start(), Err(DEngineError::InvokeDepthExceeded(2)));
+    assert_eq!(engine.invoke_depth(), 0);
+}
+
+#[test]
+fn test_invoke_debot_result() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let abi = json!({
+        "functions": [{
+            "name": "invokeChild",
+            "inputs": [],
+            "outputs": [],
+        }, {
+            "name": "onResult",
+            "inputs": [{ "name": "value", "type": "uint8" }, { "name": "ok", "type": "bool" }],
+            "outputs": [],
+        }],
+    });
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("invokeChild", 4, 1, "func=onResult")]),
+        context_json(1, "Done", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+    ]);
+    let mut return_result = action_json("returnResult", 10, STATE_EXIT, "instant");
+    return_result["desc"] = json!(hex::encode(r#"{"value":"0x2a","ok":true}"#));
+    let child_contexts = json!([
+        context_json(0, "Child menu", vec![action_json("Quit", 0, STATE_EXIT, "")]),
+        context_json(1, "Child page", vec![return_result]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_debot(CHILD_DEBOT_ADDR, child_contexts)
+        .with_output("invokeChild", json!({
+            "debot": CHILD_DEBOT_ADDR,
+            "action": action_json("Open", 6, 1, ""),
+        }))
+        .with_output("onResult", json!({}));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(Some(&abi), backend, &browser);
+    engine.start().unwrap();
+
+    let mut invoke = DAction::new(String::new(), "invokeChild".to_owned(), 0, 1);
+    invoke.action_type = AcType::Invoke;
+    invoke.set_attrs("func=onResult".to_owned());
+    engine.execute_action(&invoke).unwrap();
+    assert_eq!(engine.invoke_depth(), 0);
+    assert_eq!(engine.current_context().unwrap().desc, "Done");
+
+    let calls = calls.lock().unwrap();
+    let (_, args) = calls.iter().find(|(func, _)| func == "onResult").unwrap();
+    assert_eq!(args, &json!({ "value": "0x2a", "ok": true }));
+}
+
+#[test]
+fn test_typed_input() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    browser.lock().unwrap().inputs = vec!["300".to_owned(), "42".to_owned(), "yes".to_owned()];
+    let abi = json!({
+        "functions": [{
+            "name": "setValue",
+            "inputs": [
+                {"name": "value", "type": "uint8"},
+                {"name": "opts", "type": "tuple", "components": [{"name": "bounce", "type": "bool"}]},
+            ],
+            "outputs": [],
+        }],
+    });
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("setValue", 1, STATE_EXIT, "")]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts).with_output("setValue", json!(null));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(Some(&abi), backend, &browser);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+
+    let requests = browser.lock().unwrap().requests.clone();
+    let names: Vec<&str> = requests.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, vec!["value", "value", "opts.bounce"]);
+    assert_eq!(requests[0].abi_type, "uint8");
+    assert_eq!(requests[0].error, None);
+    assert!(requests[1].error.is_some());
+
+    let calls = calls.lock().unwrap();
+    let (_, args) = calls.iter().find(|(func, _)| func == "setValue").unwrap();
+    assert_eq!(args, &json!({"value": "0x2a", "opts": {"bounce": true}}));
+}
+
+#[test]
+fn test_cancel_input() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    browser.lock().unwrap().inputs = vec!["1".to_owned()];
+    let abi = json!({
+        "functions": [{
+            "name": "setValues",
+            "inputs": [{"name": "a", "type": "uint8"}, {"name": "b", "type": "uint8"}],
+            "outputs": [],
+        }],
+    });
+    let contexts = json!([
+        context_json(0, "Main menu", vec![action_json("setValues", 1, STATE_EXIT, "")]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts).with_output("setValues", json!(null));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(Some(&abi), backend, &browser);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+
+    assert_eq!(browser.lock().unwrap().requests.len(), 2);
+    assert!(calls.lock().unwrap().iter().all(|(func, _)| func != "setValues"));
+    assert_eq!(engine.current_state(), STATE_ZERO);
+}
+
+#[test]
+fn test_send_signed_message() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let backend = MockBackend::send_transfer();
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+    assert_eq!(engine.current_state(), STATE_EXIT);
+
+    let confirmed = browser.lock().unwrap().confirmed.clone();
+    assert_eq!(confirmed.len(), 1);
+    assert_eq!(confirmed[0].func, "transfer");
+    assert_eq!(confirmed[0].args.len(), 1);
+    assert_eq!(confirmed[0].args[0].name, "value");
+    assert_eq!(confirmed[0].args[0].value, json!("0x1"));
+    assert!(confirmed[0].signed_by_user);
+    assert!(!confirmed[0].has_state_init);
+
+    let signed = browser.lock().unwrap().signed.clone();
+    assert_eq!(signed, vec![SignPurpose::Message {
+        dest: TonAddress::from_str(DEBOT_ADDR).unwrap().to_string(),
+        func: "transfer".to_owned(),
+    }]);
+    let calls = calls.lock().unwrap();
+    let (_, sent) = calls.iter().find(|(func, _)| func == "transfer").unwrap();
+    assert_eq!(sent, &json!({ "messageId": "signed" }));
+}
+
+#[test]
+fn test_reject_message() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    browser.lock().unwrap().approve = false;
+    let backend = MockBackend::send_transfer();
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+    assert_eq!(engine.current_state(), STATE_ZERO);
+
+    let browser = browser.lock().unwrap();
+    assert_eq!(browser.confirmed.len(), 1);
+    assert!(browser.signed.is_empty());
+    assert!(calls.lock().unwrap().iter().all(|(func, _)| func != "transfer"));
+}
+
+#[test]
+fn test_dry_run() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let backend = MockBackend::send_transfer();
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.set_dry_run(true);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+    assert_eq!(engine.current_state(), STATE_EXIT);
+
+    let calls = calls.lock().unwrap();
+    let transfers: Vec<_> = calls.iter().filter(|(func, _)| func == "transfer").collect();
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].1, json!({ "emulated": "signed" }));
+    let browser = browser.lock().unwrap();
+    assert_eq!(browser.signed.len(), 1);
+    let dry_run_logs: Vec<&str> = browser.logs.iter()
+        .map(|log| log.as_str())
+        .skip_while(|log| !log.starts_with("dry run"))
+        .collect();
+    assert_eq!(dry_run_logs, vec![
+        "dry run: transfer succeeded, message signed was not sent",
+        "fees: 10 nanotokens (gas 3, storage 2, forward 5)",
+        r#"Result: {"result":"0x1"}"#,
+    ]);
+}
+
+#[test]
+fn test_estimate_fees_routine() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![
+            action_json("estimateFees", 10, STATE_EXIT, "args=getFeeArgs,func=setFees"),
+        ]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_output("getFeeArgs", json!({
+            "addr": DEBOT_ADDR,
+            "abi": hex::encode("{}"),
+            "func": "transfer",
+            "args": { "value": "0x1" },
+        }))
+        .with_output("setFees", json!({}));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+
+    let calls = calls.lock().unwrap();
+    assert!(calls.contains(&("transfer".to_owned(), json!({ "emulated": "unsigned" }))));
+    let fees = json!({ "total": "10", "gas": "3", "storage": "2", "forward": "5" });
+    assert!(calls.contains(&("setFees".to_owned(), fees)));
+    assert!(browser.lock().unwrap().signed.is_empty());
+}
+
+#[test]
+fn test_estimate_fees_before_send() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let backend = MockBackend::send_transfer();
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.set_estimate_fees(true);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+    assert_eq!(engine.current_state(), STATE_EXIT);
+
+    let calls = calls.lock().unwrap();
+    let transfers: Vec<_> = calls.iter()
+        .filter(|(func, _)| func == "transfer")
+        .map(|(_, args)| args.clone())
+        .collect();
+    assert_eq!(transfers, vec![json!({ "emulated": "signed" }), json!({ "messageId": "signed" })]);
+    let browser = browser.lock().unwrap();
+    assert_eq!(browser.confirmed.len(), 1);
+    assert_eq!(browser.confirmed[0].fees, Some(MessageFees { total: 10, gas: 3, storage: 2, forward: 5 }));
+    // the same message is emulated and sent, so it is signed once
+    let dest = TonAddress::from_str(DEBOT_ADDR).unwrap().to_string();
+    assert_eq!(browser.signed, vec![SignPurpose::Message { dest, func: "transfer".to_owned() }]);
+}
+
+#[test]
+fn test_custom_routine() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![
+            action_json("echo", 10, STATE_CURRENT, "func=setEcho"),
+            action_json("listRoutines", 10, STATE_CURRENT, "func=setRoutines"),
+        ]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_output("setEcho", json!({}))
+        .with_output("setRoutines", json!({}));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.register_routine("echo", Box::new(
+        |_: &dyn DebotBackend, arg: &str, _: Option<&dyn Signer>| Ok(arg.to_uppercase())
+    )).unwrap();
+    assert_eq!(
+        engine.register_routine("returnResult", Box::new(
+            |_: &dyn DebotBackend, arg: &str, _: Option<&dyn Signer>| Ok(arg.to_owned())
+        )),
+        Err(DEngineError::ReservedRoutine("returnResult".to_owned())),
+    );
+    engine.start().unwrap();
+    assert_eq!(engine.validate(), vec![]);
+    assert!(engine.routines().contains(&"echo".to_owned()));
+
+    let contexts = engine.contexts().to_vec();
+    for action in contexts[0].actions.iter() {
+        let mut action = action.clone();
+        action.desc = "hello".to_owned();
+        engine.execute_action(&action).unwrap();
+    }
+    let calls = calls.lock().unwrap();
+    assert!(calls.contains(&("setEcho".to_owned(), json!({ "arg1": "HELLO" }))));
+    let (_, routines) = calls.iter().find(|(func, _)| func == "setRoutines").unwrap();
+    let routines = routines["arg1"].as_str().unwrap();
+    assert_eq!(
+        routines,
+        concat!(
+            "convertTokens,echo,estimateFees,getAccountInfo,getAccountState,getBalance,",
+            "listRoutines,loadBocFromFile,returnResult,signHash",
+        ),
+    );
+}
+
+#[test]
+fn test_replace_routines() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let mut engine = engine(None, MockBackend::new(), &browser);
+    assert!(engine.unregister_routine("getBalance"));
+    assert!(!engine.unregister_routine("getBalance"));
+    assert!(!engine.routines().contains(&"getBalance".to_owned()));
+
+    let mut routines = RoutineRegistry::new();
+    routines.register("echo", Box::new(
+        |_: &dyn DebotBackend, arg: &str, _: Option<&dyn Signer>| Ok(arg.to_owned())
+    ));
+    engine.set_routines(routines).unwrap();
+    assert_eq!(engine.routines(), vec!["echo", "listRoutines", "returnResult"]);
+
+    let mut routines = RoutineRegistry::new();
+    routines.register("listRoutines", Box::new(
+        |_: &dyn DebotBackend, arg: &str, _: Option<&dyn Signer>| Ok(arg.to_owned())
+    ));
+    assert_eq!(
+        engine.set_routines(routines).err(),
+        Some(DEngineError::ReservedRoutine("listRoutines".to_owned())),
+    );
+    assert_eq!(engine.routines(), vec!["echo", "listRoutines", "returnResult"]);
+}
+
+#[test]
+fn test_get_account_info() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![
+            action_json("getAccountInfo", 10, STATE_CURRENT, "args=getInfoArgs,func=setInfo"),
+        ]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_account(json!({
+            "id": DEBOT_ADDR,
+            "acc_type_name": "Active",
+            "balance": "0x100",
+            "code_hash": "abcd",
+        }))
+        .with_output("getInfoArgs", json!({ "addr": DEBOT_ADDR, "fields": ["acc_type_name", "code_hash"] }))
+        .with_output("setInfo", json!({}));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.start().unwrap();
+    let action = engine.current_context().unwrap().actions[0].clone();
+    engine.execute_action(&action).unwrap();
+
+    let (_, info) = calls.lock().unwrap().iter().find(|(func, _)| func == "setInfo").cloned().unwrap();
+    assert_eq!(info, json!({ "exists": true, "acc_type_name": "Active", "code_hash": "abcd" }));
+}
+
+#[test]
+fn test_routine_result_to_typed_setter() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let abi = json!({
+        "ABI version": 2,
+        "functions": [{
+            "name": "setAccount",
+            "inputs": [{ "name": "exists", "type": "bool" }, { "name": "balance", "type": "uint128" }],
+            "outputs": [],
+        }, {
+            "name": "setState",
+            "inputs": [{ "name": "arg1", "type": "cell" }],
+            "outputs": [],
+        }],
+    });
+    let contexts = json!([
+        context_json(0, "Main menu", vec![
+            action_json("getAccountInfo", 10, STATE_CURRENT, "args=getInfoArgs,func=setAccount"),
+            action_json("getAccountState", 10, STATE_CURRENT, "args=getInfoArgs,func=setState"),
+        ]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_account(json!({ "id": DEBOT_ADDR, "balance": "0x100", "boc": EMPTY_CELL }))
+        .with_output("getInfoArgs", json!({ "addr": DEBOT_ADDR, "fields": "balance" }))
+        .with_output("setAccount", json!({}))
+        .with_output("setState", json!({}));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(Some(&abi), backend, &browser);
+    engine.start().unwrap();
+    let contexts = engine.contexts().to_vec();
+    for action in contexts[0].actions.iter() {
+        engine.execute_action(action).unwrap();
+    }
+
+    let calls = calls.lock().unwrap();
+    let (_, args) = calls.iter().find(|(func, _)| func == "setAccount").unwrap();
+    assert_eq!(args, &json!({ "exists": true, "balance": "0x100" }));
+    // setter gets exactly its abi inputs
+    let mut inputs: Vec<&str> = abi["functions"][0]["inputs"].as_array().unwrap().iter()
+        .map(|input| input["name"].as_str().unwrap())
+        .collect();
+    inputs.sort();
+    let names: Vec<&str> = args.as_object().unwrap().keys().map(|name| name.as_str()).collect();
+    assert_eq!(names, inputs);
+    assert!(calls.contains(&("setState".to_owned(), json!({ "arg1": EMPTY_CELL }))));
+}
+
+#[test]
+fn test_get_account_not_found() {
+    let browser = Arc::new(Mutex::new(TestBrowser::new()));
+    let contexts = json!([
+        context_json(0, "Main menu", vec![
+            action_json("getAccountInfo", 10, STATE_CURRENT, "args=getInfoArgs,func=setInfo"),
+            action_json("getAccountState", 10, STATE_CURRENT, "args=getInfoArgs,func=setState"),
+        ]),
+    ]);
+    let backend = MockBackend::with_contexts(contexts)
+        .with_account(json!({ "id": DEBOT_ADDR, "balance": "0x100" }))
+        .with_output("getInfoArgs", json!({
+            "addr": CHILD_DEBOT_ADDR,
+            "fields": ["acc_type_name", "balance", "code"],
+        }))
+        .with_output("setInfo", json!({}))
+        .with_output("setState", json!({}));
+    let calls = Arc::clone(&backend.calls);
+    let mut engine = engine(None, backend, &browser);
+    engine.start().unwrap();
+    let contexts = engine.contexts().to_vec();
+    for action in contexts[0].actions.iter() {
+        engine.execute_action(action).unwrap();
+    }
+
+    let calls = calls.lock().unwrap();
+    let (_, info) = calls.iter().find(|(func, _)| func == "setInfo").unwrap();
+    assert_eq!(info, &json!({
+        "exists": false,
+        "acc_type_name": "NonExist",
+        "balance": "0x0",
+        "code": EMPTY_CELL,
+    }));
+    assert!(calls.contains(&("setState".to_owned(), json!({ "arg1": EMPTY_CELL }))));
+}